@@ -0,0 +1,120 @@
+/*!
+Minimal stand-in for a web3signer instance, to try `remote_signer` networks locally.
+
+Serves `POST /api/v1/eth1/sign/{address}` and `GET /upcheck`, signing with `MOCK_SIGNER_KEY`
+(defaults to the first anvil dev account) on `127.0.0.1:MOCK_SIGNER_PORT` (default 9000).
+
+```bash
+cargo run --example mock_remote_signer
+```
+
+The `remote_signer` tests run the same handler in-process.
+*/
+
+use alloy::{
+    primitives::{keccak256, Address},
+    signers::{local::PrivateKeySigner, SignerSync},
+};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{str::FromStr, sync::Arc};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+
+const ANVIL_KEY: &str = "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+
+#[derive(Deserialize)]
+struct SignRequest {
+    data: String,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let key = std::env::var("MOCK_SIGNER_KEY").unwrap_or_else(|_| ANVIL_KEY.to_string());
+    let port = std::env::var("MOCK_SIGNER_PORT").unwrap_or_else(|_| "9000".to_string());
+    let signer = Arc::new(PrivateKeySigner::from_str(&key).context("Invalid MOCK_SIGNER_KEY")?);
+
+    let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).await?;
+    println!("Mock remote signer for {} on http://127.0.0.1:{}", signer.address(), port);
+
+    let address = signer.address();
+    serve(listener, signer, address).await
+}
+
+/// Answers sign requests for `address` with `signer`, normally the signer's own address
+pub async fn serve(
+    listener: TcpListener,
+    signer: Arc<PrivateKeySigner>,
+    address: Address,
+) -> Result<()> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let signer = signer.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream, &signer, address).await {
+                eprintln!("Request failed: {}", e);
+            }
+        });
+    }
+}
+
+async fn handle(stream: TcpStream, signer: &PrivateKeySigner, address: Address) -> Result<()> {
+    let mut reader = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).await?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse()?;
+            }
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).await?;
+
+    let sign_path = format!("/api/v1/eth1/sign/{}", address);
+    let (status, response) = match (method.as_str(), path.as_str()) {
+        ("GET", "/upcheck") => ("200 OK", "OK".to_string()),
+        ("POST", path) if path.eq_ignore_ascii_case(&sign_path) => match sign(signer, &body) {
+            Ok(signature) => ("200 OK", signature),
+            Err(e) => ("400 Bad Request", e.to_string()),
+        },
+        ("POST", _) => ("404 Not Found", "Signer not found".to_string()),
+        _ => ("404 Not Found", String::new()),
+    };
+    println!("{} {} -> {}", method, path, status);
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        response.len(),
+        response
+    );
+    reader.into_inner().write_all(response.as_bytes()).await?;
+
+    Ok(())
+}
+
+/// web3signer signs the keccak hash of the posted data
+fn sign(signer: &PrivateKeySigner, body: &[u8]) -> Result<String> {
+    let request: SignRequest = serde_json::from_slice(body)?;
+    let data = hex::decode(request.data.trim_start_matches("0x"))?;
+    let signature = signer.sign_hash_sync(&keccak256(&data))?;
+
+    Ok(format!("0x{}", hex::encode(signature.as_bytes())))
+}
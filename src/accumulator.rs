@@ -0,0 +1,234 @@
+//! Minimal reader for the Pyth accumulator update format ("PNAU") served by Hermes.
+//!
+//! A single Hermes response carries one update for every requested feed. Each price update
+//! is proven independently against the VAA merkle root, so the keeper can keep only the
+//! updates a network actually needs and push exactly the prices it evaluated.
+
+use alloy::primitives::Bytes;
+use anyhow::{bail, Context, Result};
+
+const MAGIC: &[u8; 4] = b"PNAU";
+const MAJOR_VERSION: u8 = 1;
+const UPDATE_TYPE_WORMHOLE_MERKLE: u8 = 0;
+const PRICE_FEED_MESSAGE: u8 = 0;
+const PROOF_NODE_SIZE: usize = 20;
+
+pub struct AccumulatorUpdate<'a> {
+    /// Everything up to and including the VAA, copied as-is when re-encoding
+    header: &'a [u8],
+    updates: Vec<PriceUpdate<'a>>,
+}
+
+struct PriceUpdate<'a> {
+    feed_id: String,
+    /// Length-prefixed message followed by its merkle proof
+    raw: &'a [u8],
+}
+
+impl<'a> AccumulatorUpdate<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self> {
+        let mut reader = Reader { data, offset: 0 };
+
+        if reader.take(4)? != MAGIC {
+            bail!("Not an accumulator update (bad magic)");
+        }
+        let major_version = reader.u8()?;
+        if major_version != MAJOR_VERSION {
+            bail!("Unsupported accumulator update version {}", major_version);
+        }
+        reader.u8()?; // minor version
+        let trailing_header_size = reader.u8()? as usize;
+        reader.take(trailing_header_size)?;
+
+        let update_type = reader.u8()?;
+        if update_type != UPDATE_TYPE_WORMHOLE_MERKLE {
+            bail!("Unsupported accumulator update type {}", update_type);
+        }
+        let vaa_size = reader.u16()? as usize;
+        reader.take(vaa_size)?;
+        let header = &data[..reader.offset];
+
+        let num_updates = reader.u8()?;
+        let mut updates = Vec::with_capacity(num_updates as usize);
+        for _ in 0..num_updates {
+            let start = reader.offset;
+            let message_size = reader.u16()? as usize;
+            let message = reader.take(message_size)?;
+            let proof_size = reader.u8()? as usize;
+            reader.take(proof_size * PROOF_NODE_SIZE)?;
+
+            if message.first() != Some(&PRICE_FEED_MESSAGE) || message.len() < 33 {
+                bail!("Accumulator update contains a non price feed message");
+            }
+            let feed_id = hex::encode(&message[1..33]);
+
+            updates.push(PriceUpdate { feed_id, raw: &data[start..reader.offset] });
+        }
+
+        if reader.offset != data.len() {
+            bail!("Accumulator update has {} trailing bytes", data.len() - reader.offset);
+        }
+
+        Ok(Self { header, updates })
+    }
+
+    /// Feed ids (lowercase hex, no `0x`) carried by this update
+    pub fn feed_ids(&self) -> impl Iterator<Item = &str> {
+        self.updates.iter().map(|u| u.feed_id.as_str())
+    }
+
+    /// Re-encodes the update keeping only the given feeds
+    pub fn retain(&self, feed_ids: &[String]) -> Bytes {
+        let kept: Vec<&PriceUpdate> =
+            self.updates.iter().filter(|u| feed_ids.contains(&u.feed_id)).collect();
+
+        let mut encoded = Vec::with_capacity(
+            self.header.len() + 1 + kept.iter().map(|u| u.raw.len()).sum::<usize>(),
+        );
+        encoded.extend_from_slice(self.header);
+        encoded.push(kept.len() as u8);
+        for update in kept {
+            encoded.extend_from_slice(update.raw);
+        }
+
+        Bytes::from(encoded)
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.offset.checked_add(len).filter(|end| *end <= self.data.len());
+        let end = end.context("Accumulator update is truncated")?;
+        let bytes = &self.data[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // No captured Hermes response is checked in, so the fixture is assembled field by field
+    // following the PNAU layout: header, VAA, then per feed a price feed message and its proof
+    const ETH_USD: &str = "ff61491a931112ddf1bd8147cd1b641375f79f5825126d665480874634fd0ace";
+    const BTC_USD: &str = "e62df6c8b4a85fe1a67db44dc12de5db330f7ac66b72dc658afedf0f4a415b43";
+    const USDC_USD: &str = "eaa020c61cc479712813461ce153894a96a6c00b21ed0cfc2798d1f9a9e9c94a";
+
+    fn price_update(feed_id: &str, price: i64, proof_nodes: u8) -> Vec<u8> {
+        let mut message = vec![PRICE_FEED_MESSAGE];
+        message.extend(hex::decode(feed_id).unwrap());
+        message.extend(price.to_be_bytes());
+        message.extend(1_500_000u64.to_be_bytes()); // conf
+        message.extend((-8i32).to_be_bytes()); // expo
+        message.extend(1_730_000_000i64.to_be_bytes()); // publish_time
+        message.extend(1_729_999_999i64.to_be_bytes()); // prev_publish_time
+        message.extend(price.to_be_bytes()); // ema_price
+        message.extend(1_400_000u64.to_be_bytes()); // ema_conf
+
+        let mut update = (message.len() as u16).to_be_bytes().to_vec();
+        update.extend(message);
+        update.push(proof_nodes);
+        update.extend((0..proof_nodes as usize * PROOF_NODE_SIZE).map(|i| i as u8));
+        update
+    }
+
+    fn blob(feeds: &[(&str, i64)]) -> Vec<u8> {
+        let vaa: Vec<u8> = (0..120u8).collect();
+
+        let mut data = MAGIC.to_vec();
+        data.extend([MAJOR_VERSION, 0, 0, UPDATE_TYPE_WORMHOLE_MERKLE]);
+        data.extend((vaa.len() as u16).to_be_bytes());
+        data.extend(vaa);
+        data.push(feeds.len() as u8);
+        for (i, (feed_id, price)) in feeds.iter().enumerate() {
+            data.extend(price_update(feed_id, *price, 10 + i as u8));
+        }
+        data
+    }
+
+    fn three_feeds() -> Vec<u8> {
+        blob(&[(ETH_USD, 262_512_345_678), (BTC_USD, 6_812_300_000_000), (USDC_USD, 99_990_000)])
+    }
+
+    #[test]
+    fn parses_feed_ids() {
+        let data = three_feeds();
+        let update = AccumulatorUpdate::parse(&data).unwrap();
+
+        assert_eq!(update.feed_ids().collect::<Vec<_>>(), vec![ETH_USD, BTC_USD, USDC_USD]);
+    }
+
+    #[test]
+    fn retaining_all_feeds_is_byte_identical() {
+        let data = three_feeds();
+        let update = AccumulatorUpdate::parse(&data).unwrap();
+        let all: Vec<String> = update.feed_ids().map(str::to_string).collect();
+
+        assert_eq!(update.retain(&all).as_ref(), data.as_slice());
+    }
+
+    #[test]
+    fn retaining_a_subset_reparses_to_that_subset() {
+        let data = three_feeds();
+        let update = AccumulatorUpdate::parse(&data).unwrap();
+
+        let retained = update.retain(&[USDC_USD.to_string(), ETH_USD.to_string()]);
+        let reparsed = AccumulatorUpdate::parse(&retained).unwrap();
+
+        // Original order, with the messages and proofs copied untouched
+        assert_eq!(reparsed.feed_ids().collect::<Vec<_>>(), vec![ETH_USD, USDC_USD]);
+        let header_len = data.len()
+            - 1
+            - price_update(ETH_USD, 262_512_345_678, 10).len()
+            - price_update(BTC_USD, 6_812_300_000_000, 11).len()
+            - price_update(USDC_USD, 99_990_000, 12).len();
+        let mut expected = data[..header_len].to_vec();
+        expected.push(2);
+        expected.extend(price_update(ETH_USD, 262_512_345_678, 10));
+        expected.extend(price_update(USDC_USD, 99_990_000, 12));
+        assert_eq!(retained.as_ref(), expected.as_slice());
+    }
+
+    #[test]
+    fn rejects_truncated_input() {
+        let data = three_feeds();
+        for len in 0..data.len() {
+            assert!(AccumulatorUpdate::parse(&data[..len]).is_err(), "accepted {} bytes", len);
+        }
+    }
+
+    #[test]
+    fn rejects_trailing_bytes() {
+        let mut data = three_feeds();
+        data.push(0);
+
+        let error = AccumulatorUpdate::parse(&data).err().unwrap();
+        assert!(error.to_string().contains("1 trailing bytes"));
+    }
+
+    #[test]
+    fn rejects_bad_magic_and_version() {
+        let mut data = three_feeds();
+        data[0] = b'X';
+        assert!(AccumulatorUpdate::parse(&data).is_err());
+
+        let mut data = three_feeds();
+        data[4] = MAJOR_VERSION + 1;
+        assert!(AccumulatorUpdate::parse(&data).is_err());
+    }
+}
@@ -0,0 +1,132 @@
+use crate::config::{BalanceConfig, Config};
+use crate::utils::alert;
+use chrono::{DateTime, Duration, Utc};
+use log::info;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BalanceLevel {
+    #[default]
+    Ok,
+    Warn,
+    /// Deviation updates are paused, heartbeats continue
+    Critical,
+}
+
+/// Classified balance reading of a network's signer
+#[derive(Debug, Clone, Copy)]
+pub struct BalanceReport {
+    pub level: BalanceLevel,
+    /// Days until the balance runs out at the recent spend rate, `None` without spend
+    pub runway_days: Option<f64>,
+}
+
+/// Signer balances per network against the configured thresholds, and the native spend
+/// the runway projection is based on
+#[derive(Debug)]
+pub struct BalanceMonitor {
+    wallets: HashMap<String, Mutex<Wallet>>,
+}
+
+#[derive(Debug)]
+struct Wallet {
+    config: BalanceConfig,
+    level: BalanceLevel,
+    spend: VecDeque<(DateTime<Utc>, f64)>,
+}
+
+impl BalanceMonitor {
+    pub fn new(config: &Config) -> Self {
+        let wallets = config
+            .networks
+            .iter()
+            .map(|network| {
+                let wallet = Wallet {
+                    config: network.balance.clone(),
+                    level: BalanceLevel::Ok,
+                    spend: VecDeque::new(),
+                };
+                (network.name.clone(), Mutex::new(wallet))
+            })
+            .collect();
+
+        Self { wallets }
+    }
+
+    pub fn record_spend(&self, network: &str, native: f64) {
+        if let Some(wallet) = self.wallets.get(network) {
+            wallet.lock().unwrap().spend.push_back((Utc::now(), native));
+        }
+    }
+
+    /// Classifies a fresh balance reading, alerting when the network's level changes
+    pub fn observe(&self, network: &str, balance: f64) -> BalanceReport {
+        let Some(wallet) = self.wallets.get(network) else {
+            return BalanceReport { level: BalanceLevel::Ok, runway_days: None };
+        };
+        let mut wallet = wallet.lock().unwrap();
+
+        let runway_days = wallet.runway_days(balance);
+        let config = &wallet.config;
+        let level = if config.critical_native.is_some_and(|critical| balance < critical) {
+            BalanceLevel::Critical
+        } else if config.warn_native.is_some_and(|warn| balance < warn)
+            || config
+                .warn_runway_days
+                .is_some_and(|warn| runway_days.is_some_and(|runway| runway < warn))
+        {
+            BalanceLevel::Warn
+        } else {
+            BalanceLevel::Ok
+        };
+
+        if level != wallet.level {
+            let runway =
+                runway_days.map_or_else(String::new, |days| format!(", {:.1} days left", days));
+            match level {
+                BalanceLevel::Critical => alert!(
+                    "Signer balance on {} is critical ({:.6} native{}), deviation updates paused",
+                    network,
+                    balance,
+                    runway
+                ),
+                BalanceLevel::Warn => {
+                    alert!("Signer balance on {} is low ({:.6} native{})", network, balance, runway)
+                }
+                BalanceLevel::Ok => {
+                    info!("Signer balance on {} is back to normal ({:.6} native)", network, balance)
+                }
+            }
+            wallet.level = level;
+        }
+
+        BalanceReport { level, runway_days }
+    }
+
+    pub fn is_critical(&self, network: &str) -> bool {
+        self.wallets
+            .get(network)
+            .is_some_and(|wallet| wallet.lock().unwrap().level == BalanceLevel::Critical)
+    }
+}
+
+impl Wallet {
+    fn runway_days(&mut self, balance: f64) -> Option<f64> {
+        let window = Duration::hours(self.config.runway_window_hours as i64);
+        let cutoff = Utc::now() - window;
+        while self.spend.front().is_some_and(|(time, _)| *time < cutoff) {
+            self.spend.pop_front();
+        }
+
+        let spent: f64 = self.spend.iter().map(|(_, native)| native).sum();
+        if spent <= 0.0 {
+            return None;
+        }
+
+        let daily_spend = spent * 24.0 / self.config.runway_window_hours.max(1) as f64;
+        Some(balance / daily_spend)
+    }
+}
@@ -0,0 +1,105 @@
+use crate::config::{BudgetConfig, Config};
+use crate::utils::alert;
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use std::{collections::HashMap, sync::Mutex};
+
+/// USD spent on updates (tx fee + Pyth update fee) against the daily and monthly budgets of
+/// every network and of the keeper as a whole. Spend is kept in memory and starts from
+/// zero on restart.
+#[derive(Debug)]
+pub struct SpendBudgets {
+    global: Mutex<Budget>,
+    networks: HashMap<String, Mutex<Budget>>,
+}
+
+impl SpendBudgets {
+    pub fn new(config: &Config) -> Self {
+        let networks = config
+            .networks
+            .iter()
+            .map(|network| {
+                let name = format!("{} budget", network.name);
+                (network.name.clone(), Mutex::new(Budget::new(name, network.budget.clone())))
+            })
+            .collect();
+
+        Self {
+            global: Mutex::new(Budget::new("global budget".to_string(), config.budget.clone())),
+            networks,
+        }
+    }
+
+    pub fn record(&self, network: &str, usd: f64) {
+        let now = Utc::now();
+        if let Some(budget) = self.networks.get(network) {
+            budget.lock().unwrap().record(usd, now);
+        }
+        self.global.lock().unwrap().record(usd, now);
+    }
+
+    /// The first used up budget that applies to the network, e.g. "daily Base budget"
+    pub fn exhausted(&self, network: &str) -> Option<String> {
+        let now = Utc::now();
+        self.networks
+            .get(network)
+            .and_then(|budget| budget.lock().unwrap().exhausted(now))
+            .or_else(|| self.global.lock().unwrap().exhausted(now))
+    }
+}
+
+#[derive(Debug)]
+struct Budget {
+    name: String,
+    config: BudgetConfig,
+    day: NaiveDate,
+    daily_spent: f64,
+    monthly_spent: f64,
+}
+
+impl Budget {
+    fn new(name: String, config: BudgetConfig) -> Self {
+        Self { name, config, day: Utc::now().date_naive(), daily_spent: 0.0, monthly_spent: 0.0 }
+    }
+
+    fn roll_over(&mut self, now: DateTime<Utc>) {
+        let today = now.date_naive();
+        if today == self.day {
+            return;
+        }
+        if (today.year(), today.month()) != (self.day.year(), self.day.month()) {
+            self.monthly_spent = 0.0;
+        }
+        self.daily_spent = 0.0;
+        self.day = today;
+    }
+
+    fn record(&mut self, usd: f64, now: DateTime<Utc>) {
+        self.roll_over(now);
+        let was_exhausted = self.exhausted(now);
+
+        self.daily_spent += usd;
+        self.monthly_spent += usd;
+
+        if was_exhausted.is_none() {
+            if let Some(exhausted) = self.exhausted(now) {
+                alert!(
+                    "{} exhausted (${:.2} today, ${:.2} this month), only heartbeat updates continue",
+                    exhausted,
+                    self.daily_spent,
+                    self.monthly_spent
+                );
+            }
+        }
+    }
+
+    fn exhausted(&mut self, now: DateTime<Utc>) -> Option<String> {
+        self.roll_over(now);
+        if self.config.daily_usd.is_some_and(|limit| self.daily_spent >= limit) {
+            return Some(format!("daily {}", self.name));
+        }
+        if self.config.monthly_usd.is_some_and(|limit| self.monthly_spent >= limit) {
+            return Some(format!("monthly {}", self.name));
+        }
+        None
+    }
+}
@@ -1,5 +1,24 @@
-use anyhow::{Context, Result};
+use alloy::primitives::B256;
+use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Which Pyth price drives deviation updates
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TriggerSource {
+    #[default]
+    Spot,
+    Ema,
+    /// Whichever of spot and EMA moved more
+    Max,
+}
+
+impl TriggerSource {
+    pub fn uses_ema(self) -> bool {
+        self != TriggerSource::Spot
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FeedConfig {
@@ -8,6 +27,81 @@ pub struct FeedConfig {
     pub deviation_threshold: f64,
     pub heartbeat_seconds: u64,
     pub networks: Vec<String>,
+    /// Max conf/price ratio (e.g. 0.005 = 0.5%) above which deviation updates are held back
+    #[serde(default)]
+    pub max_conf_ratio: Option<f64>,
+    /// Hermes data with an older `publish_time` is treated as stale
+    #[serde(default)]
+    pub max_source_age_seconds: Option<u64>,
+    /// Hermes data whose `publish_time` did not advance for this many consecutive snapshots (a
+    /// poll, or a stream event) is treated as stale
+    #[serde(default)]
+    pub max_unchanged_cycles: Option<u32>,
+    #[serde(default)]
+    pub trigger_source: TriggerSource,
+    /// Overrides the network's `backup_grace_seconds` for this feed
+    #[serde(default)]
+    pub backup_grace_seconds: Option<u64>,
+}
+
+impl FeedConfig {
+    /// `deviation_threshold` is configured in percent, deviations are compared in whole bps.
+    /// `load_config` rejects thresholds below 1 bps, which would round to 0.
+    pub fn deviation_threshold_bps(&self) -> u64 {
+        (self.deviation_threshold * 100.0).round() as u64
+    }
+}
+
+/// How updates are submitted to the Pyth contract
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubmissionMode {
+    /// `updatePriceFeedsIfNecessary`, a no-op when someone else already pushed the price
+    #[default]
+    IfNecessary,
+    /// `updatePriceFeeds`, always pays the update fee
+    Always,
+}
+
+/// What to do when a feed's heartbeat is longer than the contract's `getValidTimePeriod`
+/// minus the safety margin, i.e. consumers would see the price expire before it is refreshed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HeartbeatPolicy {
+    #[default]
+    Warn,
+    /// Refuse to start
+    Refuse,
+    /// Shorten the heartbeat to fit within the valid time period
+    Derive,
+}
+
+/// What to do with a network that fails the startup preflight (wrong chain id, no Pyth
+/// contract, unfunded signer, ...)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PreflightPolicy {
+    /// Refuse to start
+    #[default]
+    Refuse,
+    /// Log the report and keep running without the network
+    Disable,
+}
+
+fn default_heartbeat_safety_margin() -> u64 {
+    60
+}
+
+fn default_gas_limit_multiplier() -> f64 {
+    1.2
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_max_log_block_range() -> u64 {
+    1000
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,18 +110,306 @@ pub struct NetworkConfig {
     pub chain_id: u64,
     pub rpc_url: String,
     pub pyth_contract: String,
-    #[serde(skip_deserializing)]
-    pub private_key: String,
+    /// Resolved by `load_config`
+    #[serde(skip)]
+    pub key_source: KeySource,
+    /// Environment variable holding this network's key, instead of `PRIVATE_KEY_<NAME>`
+    #[serde(default)]
+    pub key_ref: Option<String>,
+    #[serde(default)]
+    pub keystore: Option<KeystoreConfig>,
+    #[serde(default)]
+    pub remote_signer: Option<RemoteSignerConfig>,
     pub native_feed_id: String,
     pub block_explorer: String,
+    /// Cadence of this network's update loop, defaults to the global `poll_interval_seconds`.
+    /// While streaming it only sets how often heartbeats are re-checked.
+    #[serde(default)]
+    pub poll_interval_seconds: Option<u64>,
+    /// Minimum time between stream-triggered cycles on this network, defaults to
+    /// `stream.min_cycle_interval_seconds`
+    #[serde(default)]
+    pub min_cycle_interval_seconds: Option<u64>,
+    #[serde(default)]
+    pub submission_mode: SubmissionMode,
+    #[serde(default)]
+    pub heartbeat_policy: HeartbeatPolicy,
+    #[serde(default = "default_heartbeat_safety_margin")]
+    pub heartbeat_safety_margin_seconds: u64,
+    #[serde(default)]
+    pub preflight_policy: PreflightPolicy,
+    #[serde(default)]
+    pub fees: FeeConfig,
+    #[serde(default)]
+    pub replacement: ReplacementConfig,
+    /// Upper bound on a whole update of this network, from simulation to receipt. Derived
+    /// from `replacement` when unset, see `update_timeout`.
+    #[serde(default)]
+    pub update_timeout_seconds: Option<u64>,
+    /// Headroom applied to the `eth_estimateGas` result
+    #[serde(default = "default_gas_limit_multiplier")]
+    pub gas_limit_multiplier: f64,
+    /// Updates estimated above this gas limit are not sent
+    #[serde(default)]
+    pub max_gas_limit: Option<u64>,
+    #[serde(default)]
+    pub budget: BudgetConfig,
+    #[serde(default)]
+    pub balance: BalanceConfig,
+    /// Follow `PriceFeedUpdate` events so updates by other keepers refresh our state
+    #[serde(default = "default_true")]
+    pub track_external_updates: bool,
+    /// Largest block range requested per `eth_getLogs` call
+    #[serde(default = "default_max_log_block_range")]
+    pub max_log_block_range: u64,
+    /// Backup-keeper mode: give the primary keeper this long past a heartbeat, or after a
+    /// deviation appeared, before stepping in
+    #[serde(default)]
+    pub backup_grace_seconds: Option<u64>,
+}
+
+/// Time allowed for everything around the receipt waits of a send: simulation, fee quotes and
+/// reading back the new prices
+const UPDATE_TIMEOUT_HEADROOM_SECONDS: u64 = 120;
+
+impl NetworkConfig {
+    /// `update_timeout_seconds`, or enough for a send to run through all of its replacements
+    pub fn update_timeout(&self) -> u64 {
+        self.update_timeout_seconds.unwrap_or_else(|| {
+            self.replacement.max_send_seconds().saturating_add(UPDATE_TIMEOUT_HEADROOM_SECONDS)
+        })
+    }
+}
+
+/// Encrypted V3 JSON keystore, as written by geth or `cast wallet`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeystoreConfig {
+    pub path: String,
+    /// File holding the password, prompted for on startup when unset
+    #[serde(default)]
+    pub password_file: Option<String>,
+}
+
+/// Signing service speaking the web3signer eth1 API
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RemoteSignerConfig {
+    pub url: String,
+    /// Account the service signs for
+    pub address: String,
+    #[serde(default = "default_remote_signer_timeout")]
+    pub timeout_seconds: u64,
+}
+
+fn default_remote_signer_timeout() -> u64 {
+    10
+}
+
+/// Where a network's signing key comes from
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum KeySource {
+    /// Only allowed for networks without feeds, they never sign
+    #[default]
+    None,
+    PrivateKey(String),
+    Keystore(KeystoreConfig),
+    Remote(RemoteSignerConfig),
+}
+
+/// How transaction fees are priced
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeStrategy {
+    /// `gasPrice` from `eth_gasPrice`
+    #[default]
+    Legacy,
+    /// EIP-1559 with the priority fee taken from an `eth_feeHistory` reward percentile
+    Eip1559,
+    /// EIP-1559 with a fixed priority fee of `priority_fee_gwei`
+    FixedTip,
+}
+
+/// Transaction fee settings of a network. Amounts are in gwei.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FeeConfig {
+    pub strategy: FeeStrategy,
+    /// Blocks sampled by `eth_feeHistory`
+    pub fee_history_blocks: u64,
+    /// Reward percentile used as priority fee, 0-100
+    pub priority_fee_percentile: f64,
+    pub priority_fee_gwei: f64,
+    /// `maxFeePerGas` is the next base fee times this, plus the priority fee
+    pub base_fee_multiplier: f64,
+    /// Cap on `maxFeePerGas` (or `gasPrice` for legacy)
+    pub max_fee_gwei: Option<f64>,
+    pub max_priority_fee_gwei: Option<f64>,
+}
+
+impl Default for FeeConfig {
+    fn default() -> Self {
+        Self {
+            strategy: FeeStrategy::Legacy,
+            fee_history_blocks: 10,
+            priority_fee_percentile: 50.0,
+            priority_fee_gwei: 0.01,
+            base_fee_multiplier: 2.0,
+            max_fee_gwei: None,
+            max_priority_fee_gwei: None,
+        }
+    }
+}
+
+/// Handling of update transactions that are not mined in time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReplacementConfig {
+    pub receipt_timeout_seconds: u64,
+    /// Fee increase per replacement, most nodes require at least 10%
+    pub fee_bump_percent: u64,
+    pub max_replacements: u32,
+    /// Cancel with a zero-value self-transfer once the replacements are used up
+    pub cancel_stuck: bool,
+}
+
+impl ReplacementConfig {
+    /// Longest a send can wait for receipts: every attempt, plus the cancellation
+    pub fn max_send_seconds(&self) -> u64 {
+        let attempts = self.max_replacements as u64 + 1 + self.cancel_stuck as u64;
+        self.receipt_timeout_seconds.saturating_mul(attempts)
+    }
+}
+
+impl Default for ReplacementConfig {
+    fn default() -> Self {
+        Self {
+            receipt_timeout_seconds: 120,
+            fee_bump_percent: 15,
+            max_replacements: 3,
+            cancel_stuck: false,
+        }
+    }
+}
+
+/// USD spend limits on updates (tx fee + Pyth update fee). Once one is used up, only
+/// heartbeat updates are sent until it resets.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BudgetConfig {
+    pub daily_usd: Option<f64>,
+    pub monthly_usd: Option<f64>,
+}
+
+/// Signer balance thresholds of a network, in the native token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BalanceConfig {
+    pub warn_native: Option<f64>,
+    /// Below this deviation updates are paused, heartbeats continue
+    pub critical_native: Option<f64>,
+    /// Warn when the projected runway drops below this many days
+    pub warn_runway_days: Option<f64>,
+    /// Spend window the runway projection is based on
+    pub runway_window_hours: u64,
+}
+
+impl Default for BalanceConfig {
+    fn default() -> Self {
+        Self {
+            warn_native: None,
+            critical_native: None,
+            warn_runway_days: None,
+            runway_window_hours: 24,
+        }
+    }
+}
+
+/// Hermes SSE streaming. When enabled, price events drive every network's update cycle and
+/// the `poll_interval_seconds` loops only re-check heartbeats, or poll while the stream is down.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StreamConfig {
+    pub enabled: bool,
+    /// Minimum time between two stream-triggered cycles of a network, unless the network sets
+    /// its own
+    pub min_cycle_interval_seconds: u64,
+    /// A stream that delivers nothing for this long is treated as dropped
+    pub idle_timeout_seconds: u64,
+    pub reconnect_delay_seconds: u64,
+    pub max_reconnect_delay_seconds: u64,
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_cycle_interval_seconds: 5,
+            idle_timeout_seconds: 30,
+            reconnect_delay_seconds: 1,
+            max_reconnect_delay_seconds: 60,
+        }
+    }
+}
+
+/// Failover and agreement settings shared by all Hermes endpoints
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HermesConfig {
+    /// How long a failing endpoint is skipped before it is tried again
+    pub failure_cooldown_seconds: u64,
+    pub request_timeout_seconds: u64,
+    /// Retries per cycle for transient failures (transport, 5xx, rate limits)
+    pub max_retries: u32,
+    /// Polled prices younger than this are shared between networks instead of fetched again
+    pub cache_max_age_seconds: u64,
+    pub quorum: Option<QuorumConfig>,
+}
+
+impl Default for HermesConfig {
+    fn default() -> Self {
+        Self {
+            failure_cooldown_seconds: 60,
+            request_timeout_seconds: 10,
+            max_retries: 2,
+            cache_max_age_seconds: 2,
+            quorum: None,
+        }
+    }
+}
+
+/// Only act on a feed when at least `min_agreeing` endpoints return the same publish time
+/// and prices within `price_tolerance_bps` of each other
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuorumConfig {
+    pub min_agreeing: usize,
+    #[serde(default)]
+    pub price_tolerance_bps: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub networks: Vec<NetworkConfig>,
     pub feeds: Vec<FeedConfig>,
-    pub pyth_hermes_url: String,
+    /// Single Hermes endpoint, merged into `pyth_hermes_urls` by `load_config`
+    #[serde(default)]
+    pub pyth_hermes_url: Option<String>,
+    /// Hermes endpoints in priority order
+    #[serde(default)]
+    pub pyth_hermes_urls: Vec<String>,
+    #[serde(default)]
+    pub hermes: HermesConfig,
+    /// Default cadence of the network update loops
     pub poll_interval_seconds: u64,
+    #[serde(default)]
+    pub stream: StreamConfig,
+    /// Spend limits across all networks
+    #[serde(default)]
+    pub budget: BudgetConfig,
+    /// Address serving Prometheus metrics at `/metrics`, e.g. `127.0.0.1:9100`
+    #[serde(default)]
+    pub metrics_listen: Option<String>,
+    /// Keystore used by networks without a key of their own, instead of `PRIVATE_KEY`
+    #[serde(default)]
+    pub keystore: Option<KeystoreConfig>,
 }
 
 pub fn load_config() -> Result<Config> {
@@ -37,13 +419,101 @@ pub fn load_config() -> Result<Config> {
     let mut config: Config =
         serde_json::from_str(&config_str).context("Failed to parse config.json")?;
 
-    let private_key =
-        std::env::var("PRIVATE_KEY").context("PRIVATE_KEY environment variable not set")?;
+    if let Some(url) = config.pyth_hermes_url.take() {
+        if !config.pyth_hermes_urls.contains(&url) {
+            config.pyth_hermes_urls.insert(0, url);
+        }
+    }
+    if config.pyth_hermes_urls.is_empty() {
+        bail!("No Hermes endpoint configured, set pyth_hermes_url or pyth_hermes_urls");
+    }
+    if let Some(quorum) = &config.hermes.quorum {
+        if quorum.min_agreeing == 0 || quorum.min_agreeing > config.pyth_hermes_urls.len() {
+            bail!(
+                "Hermes quorum of {} needs between 1 and {} endpoints",
+                quorum.min_agreeing,
+                config.pyth_hermes_urls.len()
+            );
+        }
+    }
+
+    for feed in &config.feeds {
+        if B256::from_str(&feed.price_feed_id).is_err() {
+            bail!("price_feed_id of {} is not a 32-byte hex id", feed.symbol);
+        }
+        if feed.deviation_threshold.is_nan() || feed.deviation_threshold < 0.01 {
+            bail!(
+                "deviation_threshold of {} is {}%, the smallest supported threshold is 0.01% (1 bps)",
+                feed.symbol,
+                feed.deviation_threshold
+            );
+        }
+    }
 
-    // Set the same private key for all networks
+    let fallback = match &config.keystore {
+        Some(keystore) => KeySource::Keystore(keystore.clone()),
+        None => std::env::var("PRIVATE_KEY").map_or(KeySource::None, KeySource::PrivateKey),
+    };
     for network in &mut config.networks {
-        network.private_key = private_key.clone();
+        // Cutting a send short abandons its transaction, and its spend goes unrecorded. Besides
+        // the receipt waits, the timeout covers simulation, fee quotes and reading back prices.
+        let min_timeout =
+            network.replacement.max_send_seconds().saturating_add(UPDATE_TIMEOUT_HEADROOM_SECONDS);
+        if network.update_timeout_seconds.is_some_and(|timeout| timeout < min_timeout) {
+            bail!(
+                "update_timeout_seconds of {} ({}s) must be at least {}s: receipt_timeout_seconds x (max_replacements + 1{}) plus {}s for the calls around the send, or be left unset",
+                network.name,
+                network.update_timeout(),
+                min_timeout,
+                if network.replacement.cancel_stuck { " + 1 for cancel_stuck" } else { "" },
+                UPDATE_TIMEOUT_HEADROOM_SECONDS
+            );
+        }
+
+        let has_feeds = config.feeds.iter().any(|feed| feed.networks.contains(&network.name));
+        network.key_source = resolve_key_source(network, &fallback)?;
+        if has_feeds && network.key_source == KeySource::None {
+            bail!(
+                "No signing key for network {}, set remote_signer, keystore, key_ref, {} or PRIVATE_KEY",
+                network.name,
+                network_key_var(&network.name)
+            );
+        }
     }
 
     Ok(config)
 }
+
+/// Key of a network, looked up in order: its `remote_signer`, its `keystore`, the env var
+/// named by `key_ref`, `PRIVATE_KEY_<NETWORK NAME>`, then the global keystore or `PRIVATE_KEY`
+fn resolve_key_source(network: &NetworkConfig, fallback: &KeySource) -> Result<KeySource> {
+    if let Some(remote_signer) = &network.remote_signer {
+        return Ok(KeySource::Remote(remote_signer.clone()));
+    }
+
+    if let Some(keystore) = &network.keystore {
+        return Ok(KeySource::Keystore(keystore.clone()));
+    }
+
+    if let Some(key_ref) = &network.key_ref {
+        let private_key = std::env::var(key_ref).with_context(|| {
+            format!("key_ref of network {} points to unset variable {}", network.name, key_ref)
+        })?;
+        return Ok(KeySource::PrivateKey(private_key));
+    }
+
+    if let Ok(private_key) = std::env::var(network_key_var(&network.name)) {
+        return Ok(KeySource::PrivateKey(private_key));
+    }
+
+    Ok(fallback.clone())
+}
+
+/// `PRIVATE_KEY_BASE` for "Base", `PRIVATE_KEY_BNB_CHAIN` for "BNB Chain"
+fn network_key_var(network_name: &str) -> String {
+    let suffix: String = network_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    format!("PRIVATE_KEY_{}", suffix)
+}
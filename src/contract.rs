@@ -1,6 +1,9 @@
 // ABI of pyth contract: https://docs.pyth.network/price-feeds/core/contract-addresses/evm
 
-use alloy::sol;
+use crate::price::PythPrice;
+use alloy::{primitives::TxHash, sol, sol_types::SolInterface, transports::TransportError};
+use std::fmt;
+use thiserror::Error;
 
 sol!(
     #[sol(rpc)]
@@ -13,6 +16,17 @@ sol!(
             "stateMutability": "payable",
             "type": "function"
         },
+        {
+            "inputs": [
+                {"internalType": "bytes[]", "name": "updateData", "type": "bytes[]"},
+                {"internalType": "bytes32[]", "name": "priceIds", "type": "bytes32[]"},
+                {"internalType": "uint64[]", "name": "publishTimes", "type": "uint64[]"}
+            ],
+            "name": "updatePriceFeedsIfNecessary",
+            "outputs": [],
+            "stateMutability": "payable",
+            "type": "function"
+        },
         {
             "inputs": [{"internalType": "bytes[]", "name": "updateData", "type": "bytes[]"}],
             "name": "getUpdateFee",
@@ -20,6 +34,18 @@ sol!(
             "stateMutability": "view",
             "type": "function"
         },
+        {
+            "inputs": [{"internalType": "bytes32", "name": "id", "type": "bytes32"}],
+            "name": "getEmaPriceUnsafe",
+            "outputs": [
+                {"internalType": "int64", "name": "price", "type": "int64"},
+                {"internalType": "uint64", "name": "conf", "type": "uint64"},
+                {"internalType": "int32", "name": "expo", "type": "int32"},
+                {"internalType": "uint256", "name": "publishTime", "type": "uint256"}
+            ],
+            "stateMutability": "view",
+            "type": "function"
+        },
         {
             "inputs": [{"internalType": "bytes32", "name": "id", "type": "bytes32"}],
             "name": "getPriceUnsafe",
@@ -31,6 +57,188 @@ sol!(
             ],
             "stateMutability": "view",
             "type": "function"
-        }
+        },
+        {
+            "inputs": [],
+            "name": "getValidTimePeriod",
+            "outputs": [{"internalType": "uint256", "name": "validTimePeriod", "type": "uint256"}],
+            "stateMutability": "view",
+            "type": "function"
+        },
+        {
+            "anonymous": false,
+            "inputs": [
+                {"indexed": true, "internalType": "bytes32", "name": "id", "type": "bytes32"},
+                {"indexed": false, "internalType": "uint64", "name": "publishTime", "type": "uint64"},
+                {"indexed": false, "internalType": "int64", "name": "price", "type": "int64"},
+                {"indexed": false, "internalType": "uint64", "name": "conf", "type": "uint64"}
+            ],
+            "name": "PriceFeedUpdate",
+            "type": "event"
+        },
+        {"inputs": [], "name": "InvalidArgument", "type": "error"},
+        {"inputs": [], "name": "InvalidUpdateDataSource", "type": "error"},
+        {"inputs": [], "name": "InvalidUpdateData", "type": "error"},
+        {"inputs": [], "name": "InsufficientFee", "type": "error"},
+        {"inputs": [], "name": "NoFreshUpdate", "type": "error"},
+        {"inputs": [], "name": "PriceFeedNotFoundWithinRange", "type": "error"},
+        {"inputs": [], "name": "PriceFeedNotFound", "type": "error"},
+        {"inputs": [], "name": "StalePrice", "type": "error"},
+        {"inputs": [], "name": "InvalidWormholeVaa", "type": "error"},
+        {"inputs": [], "name": "InvalidGovernanceMessage", "type": "error"},
+        {"inputs": [], "name": "InvalidGovernanceTarget", "type": "error"},
+        {"inputs": [], "name": "InvalidGovernanceDataSource", "type": "error"},
+        {"inputs": [], "name": "OldGovernanceMessage", "type": "error"},
+        {"inputs": [], "name": "InvalidWormholeAddressToSet", "type": "error"}
     ]"#
 );
+
+impl From<IPythContract::getPriceUnsafeReturn> for PythPrice {
+    fn from(result: IPythContract::getPriceUnsafeReturn) -> Self {
+        Self {
+            price: result.price,
+            conf: result.conf,
+            expo: result.expo,
+            publish_time: result.publishTime.try_into().unwrap_or(0),
+        }
+    }
+}
+
+impl From<IPythContract::getEmaPriceUnsafeReturn> for PythPrice {
+    fn from(result: IPythContract::getEmaPriceUnsafeReturn) -> Self {
+        Self {
+            price: result.price,
+            conf: result.conf,
+            expo: result.expo,
+            publish_time: result.publishTime.try_into().unwrap_or(0),
+        }
+    }
+}
+
+/// Custom errors of the Pyth contract (`PythErrors.sol`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PythError {
+    InvalidArgument,
+    InvalidUpdateDataSource,
+    InvalidUpdateData,
+    InsufficientFee,
+    NoFreshUpdate,
+    PriceFeedNotFoundWithinRange,
+    PriceFeedNotFound,
+    StalePrice,
+    InvalidWormholeVaa,
+    InvalidGovernanceMessage,
+    InvalidGovernanceTarget,
+    InvalidGovernanceDataSource,
+    OldGovernanceMessage,
+    InvalidWormholeAddressToSet,
+}
+
+impl PythError {
+    /// Decodes revert data returned by the contract
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        IPythContract::IPythContractErrors::abi_decode(data, false).ok().map(Self::from)
+    }
+
+    /// Custom error carried by a JSON-RPC error response, e.g. of `eth_call` or `eth_estimateGas`
+    pub fn from_transport(error: &TransportError) -> Option<Self> {
+        error
+            .as_error_resp()
+            .and_then(|payload| {
+                payload.as_decoded_error::<IPythContract::IPythContractErrors>(false)
+            })
+            .map(Self::from)
+    }
+}
+
+impl From<IPythContract::IPythContractErrors> for PythError {
+    fn from(error: IPythContract::IPythContractErrors) -> Self {
+        use IPythContract::IPythContractErrors as Errors;
+
+        match error {
+            Errors::InvalidArgument(_) => Self::InvalidArgument,
+            Errors::InvalidUpdateDataSource(_) => Self::InvalidUpdateDataSource,
+            Errors::InvalidUpdateData(_) => Self::InvalidUpdateData,
+            Errors::InsufficientFee(_) => Self::InsufficientFee,
+            Errors::NoFreshUpdate(_) => Self::NoFreshUpdate,
+            Errors::PriceFeedNotFoundWithinRange(_) => Self::PriceFeedNotFoundWithinRange,
+            Errors::PriceFeedNotFound(_) => Self::PriceFeedNotFound,
+            Errors::StalePrice(_) => Self::StalePrice,
+            Errors::InvalidWormholeVaa(_) => Self::InvalidWormholeVaa,
+            Errors::InvalidGovernanceMessage(_) => Self::InvalidGovernanceMessage,
+            Errors::InvalidGovernanceTarget(_) => Self::InvalidGovernanceTarget,
+            Errors::InvalidGovernanceDataSource(_) => Self::InvalidGovernanceDataSource,
+            Errors::OldGovernanceMessage(_) => Self::OldGovernanceMessage,
+            Errors::InvalidWormholeAddressToSet(_) => Self::InvalidWormholeAddressToSet,
+        }
+    }
+}
+
+impl fmt::Display for PythError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Why an update transaction failed
+#[derive(Debug, Error)]
+pub enum UpdateError {
+    /// Simulation showed the contract would revert
+    #[error("Pyth contract rejected the update with {0}")]
+    Rejected(PythError),
+    /// The transaction was mined but reverted. `reason` comes from tracing or replaying it.
+    #[error("update transaction {tx_hash:?} reverted in block {block} ({})",
+        reason.map_or_else(|| "unknown reason".to_string(), |reason| reason.to_string()))]
+    Reverted { tx_hash: TxHash, block: u64, reason: Option<PythError> },
+    /// The update was never mined, the self-transfer replacing it was. `fee_wei` is what the
+    /// cancellation cost.
+    #[error("update transaction not mined, cancelled by {tx_hash:?}")]
+    Cancelled { tx_hash: TxHash, fee_wei: u128 },
+    #[error("estimated gas of {estimate} exceeds the network's cap of {cap}")]
+    GasCapExceeded { estimate: u64, cap: u64 },
+    /// The network's `update_timeout_seconds` ran out
+    #[error("update timed out after {seconds}s")]
+    Timeout { seconds: u64 },
+}
+
+impl UpdateError {
+    /// Short label for logs and metrics
+    pub fn reason(&self) -> String {
+        match self {
+            Self::Rejected(error) | Self::Reverted { reason: Some(error), .. } => error.to_string(),
+            Self::Reverted { reason: None, .. } => "Reverted".to_string(),
+            Self::Cancelled { .. } => "Cancelled".to_string(),
+            Self::GasCapExceeded { .. } => "GasCapExceeded".to_string(),
+            Self::Timeout { .. } => "Timeout".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::primitives::hex;
+
+    #[test]
+    fn decodes_pyth_custom_errors() {
+        assert_eq!(PythError::decode(&hex!("de2c57fa")), Some(PythError::NoFreshUpdate));
+        assert_eq!(PythError::decode(&hex!("025dbdd4")), Some(PythError::InsufficientFee));
+        assert_eq!(PythError::decode(&hex!("19abf40e")), Some(PythError::StalePrice));
+        assert_eq!(PythError::decode(&hex!("14aebe68")), Some(PythError::PriceFeedNotFound));
+        assert_eq!(PythError::decode(&hex!("e69ffece")), Some(PythError::InvalidUpdateData));
+    }
+
+    #[test]
+    fn ignores_other_revert_data() {
+        assert_eq!(PythError::decode(&[]), None);
+        assert_eq!(PythError::decode(&hex!("deadbeef")), None);
+        // Error(string) with "nope"
+        let error_string = hex!(
+            "08c379a0"
+            "0000000000000000000000000000000000000000000000000000000000000020"
+            "0000000000000000000000000000000000000000000000000000000000000004"
+            "6e6f706500000000000000000000000000000000000000000000000000000000"
+        );
+        assert_eq!(PythError::decode(&error_string), None);
+    }
+}
@@ -0,0 +1,212 @@
+use crate::config::{FeeConfig, FeeStrategy};
+use alloy::{
+    eips::BlockNumberOrTag, network::TransactionBuilder, providers::Provider,
+    rpc::types::TransactionRequest, transports::Transport,
+};
+use anyhow::{Context, Result};
+use log::warn;
+use std::fmt;
+
+/// Fee fields of an update transaction, in wei
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GasPricing {
+    Legacy { gas_price: u128 },
+    Eip1559 { max_fee_per_gas: u128, max_priority_fee_per_gas: u128 },
+}
+
+impl GasPricing {
+    /// Fees raised by `percent`, and at least 1 wei so zero fees go up too, for a same-nonce
+    /// replacement within the network's caps. `None` if the caps leave no room for a raise.
+    pub fn bumped(&self, percent: u64, config: &FeeConfig) -> Option<GasPricing> {
+        let bump = |value: u128, limit: Option<f64>| {
+            let raised = value + (value * percent as u128 / 100).max(1);
+            let raised = limit.map_or(raised, |limit| raised.min(from_gwei(limit)));
+            (raised > value).then_some(raised)
+        };
+
+        match *self {
+            Self::Legacy { gas_price } => {
+                Some(Self::Legacy { gas_price: bump(gas_price, config.max_fee_gwei)? })
+            }
+            Self::Eip1559 { max_fee_per_gas, max_priority_fee_per_gas } => {
+                let max_fee_per_gas = bump(max_fee_per_gas, config.max_fee_gwei)?;
+                let max_priority_fee_per_gas =
+                    bump(max_priority_fee_per_gas, config.max_priority_fee_gwei)?
+                        .min(max_fee_per_gas);
+                Some(Self::Eip1559 { max_fee_per_gas, max_priority_fee_per_gas })
+            }
+        }
+    }
+
+    /// Each fee at least as high as in `floor`
+    pub fn at_least(&self, floor: &GasPricing) -> GasPricing {
+        match (*self, *floor) {
+            (Self::Legacy { gas_price }, Self::Legacy { gas_price: floor }) => {
+                Self::Legacy { gas_price: gas_price.max(floor) }
+            }
+            (
+                Self::Eip1559 { max_fee_per_gas, max_priority_fee_per_gas },
+                Self::Eip1559 {
+                    max_fee_per_gas: floor_max_fee,
+                    max_priority_fee_per_gas: floor_priority_fee,
+                },
+            ) => Self::Eip1559 {
+                max_fee_per_gas: max_fee_per_gas.max(floor_max_fee),
+                max_priority_fee_per_gas: max_priority_fee_per_gas.max(floor_priority_fee),
+            },
+            // The fee strategy changed since the floor was sent
+            _ => *floor,
+        }
+    }
+
+    pub fn apply(&self, tx: TransactionRequest) -> TransactionRequest {
+        match *self {
+            Self::Legacy { gas_price } => tx.with_gas_price(gas_price),
+            Self::Eip1559 { max_fee_per_gas, max_priority_fee_per_gas } => tx
+                .with_max_fee_per_gas(max_fee_per_gas)
+                .with_max_priority_fee_per_gas(max_priority_fee_per_gas),
+        }
+    }
+}
+
+impl fmt::Display for GasPricing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Legacy { gas_price } => write!(f, "gas price {:.4} gwei", to_gwei(gas_price)),
+            Self::Eip1559 { max_fee_per_gas, max_priority_fee_per_gas } => write!(
+                f,
+                "max fee {:.4} gwei, tip {:.4} gwei",
+                to_gwei(max_fee_per_gas),
+                to_gwei(max_priority_fee_per_gas)
+            ),
+        }
+    }
+}
+
+/// Prices the next transaction according to the network's fee strategy and caps
+pub async fn estimate<T, P>(provider: &P, config: &FeeConfig) -> Result<GasPricing>
+where
+    T: Transport + Clone,
+    P: Provider<T>,
+{
+    let max_fee_cap = config.max_fee_gwei.map(from_gwei);
+    let max_priority_fee_cap = config.max_priority_fee_gwei.map(from_gwei);
+
+    if config.strategy == FeeStrategy::Legacy {
+        let gas_price = provider.get_gas_price().await.context("Failed to get gas price")?;
+        return Ok(GasPricing::Legacy { gas_price: cap(gas_price, max_fee_cap, "gas price") });
+    }
+
+    let percentiles = [config.priority_fee_percentile];
+    let history = provider
+        .get_fee_history(config.fee_history_blocks.max(1), BlockNumberOrTag::Latest, &percentiles)
+        .await
+        .context("Failed to get fee history")?;
+    let base_fee = history
+        .next_block_base_fee()
+        .or_else(|| history.latest_block_base_fee())
+        .context("Fee history has no base fee, the network may not support EIP-1559")?;
+
+    let tip = match config.strategy {
+        FeeStrategy::FixedTip => from_gwei(config.priority_fee_gwei),
+        _ => {
+            let mut rewards: Vec<u128> = history
+                .reward
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|block| block.first().copied())
+                .collect();
+            rewards.sort_unstable();
+            rewards.get(rewards.len() / 2).copied().unwrap_or(0)
+        }
+    };
+    let tip = cap(tip, max_priority_fee_cap, "priority fee");
+
+    let max_fee = (base_fee as f64 * config.base_fee_multiplier) as u128 + tip;
+    let max_fee = cap(max_fee, max_fee_cap, "max fee");
+    if max_fee < base_fee {
+        warn!(
+            "Max fee cap of {:.4} gwei is below the base fee of {:.4} gwei, the transaction may not be included",
+            to_gwei(max_fee),
+            to_gwei(base_fee)
+        );
+    }
+
+    Ok(GasPricing::Eip1559 { max_fee_per_gas: max_fee, max_priority_fee_per_gas: tip.min(max_fee) })
+}
+
+fn cap(value: u128, limit: Option<u128>, what: &str) -> u128 {
+    match limit {
+        Some(limit) if value > limit => {
+            warn!(
+                "Capping {} at {:.4} gwei (quoted {:.4} gwei)",
+                what,
+                to_gwei(limit),
+                to_gwei(value)
+            );
+            limit
+        }
+        _ => value,
+    }
+}
+
+fn from_gwei(gwei: f64) -> u128 {
+    (gwei * 1e9) as u128
+}
+
+pub fn to_gwei(wei: u128) -> f64 {
+    wei as f64 / 1e9
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GWEI: u128 = 1_000_000_000;
+
+    fn eip1559(max_fee_per_gas: u128, max_priority_fee_per_gas: u128) -> GasPricing {
+        GasPricing::Eip1559 { max_fee_per_gas, max_priority_fee_per_gas }
+    }
+
+    #[test]
+    fn bumps_by_percent() {
+        let config = FeeConfig::default();
+        assert_eq!(
+            eip1559(10 * GWEI, GWEI).bumped(15, &config),
+            Some(eip1559(11_500_000_000, 1_150_000_000))
+        );
+        assert_eq!(
+            GasPricing::Legacy { gas_price: 100 }.bumped(10, &config),
+            Some(GasPricing::Legacy { gas_price: 110 })
+        );
+    }
+
+    #[test]
+    fn bumps_zero_fees() {
+        let config = FeeConfig::default();
+        assert_eq!(eip1559(GWEI / 100, 0).bumped(15, &config), Some(eip1559(11_500_000, 1)));
+        assert_eq!(
+            GasPricing::Legacy { gas_price: 0 }.bumped(15, &config),
+            Some(GasPricing::Legacy { gas_price: 1 })
+        );
+    }
+
+    #[test]
+    fn bump_stays_within_caps() {
+        let config = FeeConfig { max_fee_gwei: Some(11.0), ..FeeConfig::default() };
+        assert_eq!(
+            eip1559(10 * GWEI, GWEI).bumped(15, &config),
+            Some(eip1559(11 * GWEI, 1_150_000_000))
+        );
+        assert_eq!(eip1559(11 * GWEI, GWEI).bumped(15, &config), None);
+
+        let config = FeeConfig { max_priority_fee_gwei: Some(1.0), ..FeeConfig::default() };
+        assert_eq!(eip1559(10 * GWEI, GWEI).bumped(15, &config), None);
+    }
+
+    #[test]
+    fn at_least_raises_each_fee() {
+        assert_eq!(eip1559(10, 5).at_least(&eip1559(8, 7)), eip1559(10, 7));
+        assert_eq!(GasPricing::Legacy { gas_price: 3 }.at_least(&eip1559(8, 7)), eip1559(8, 7));
+    }
+}
@@ -0,0 +1,263 @@
+//! Pool of Hermes endpoints: priority failover with health tracking, and an optional quorum
+//! mode that cross-checks every feed across endpoints before the keeper acts on it.
+
+use crate::config::{HermesConfig, QuorumConfig, StreamConfig};
+use crate::pyth_api::{
+    self, ParsedPrice, PriceSnapshot, PythApiError, PythPriceResponse, Result, StreamEvent,
+};
+use futures::future::join_all;
+use log::{info, warn};
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::mpsc;
+
+pub struct HermesClient {
+    http_client: reqwest::Client,
+    endpoints: Vec<Endpoint>,
+    config: HermesConfig,
+}
+
+struct Endpoint {
+    url: String,
+    health: Mutex<EndpointHealth>,
+}
+
+#[derive(Default)]
+struct EndpointHealth {
+    consecutive_failures: u32,
+    unhealthy_until: Option<Instant>,
+}
+
+impl HermesClient {
+    pub fn new(urls: &[String], config: HermesConfig) -> Self {
+        let endpoints = urls
+            .iter()
+            .map(|url| Endpoint {
+                url: url.trim_end_matches('/').to_string(),
+                health: Mutex::new(EndpointHealth::default()),
+            })
+            .collect();
+
+        Self { http_client: reqwest::Client::new(), endpoints, config }
+    }
+
+    pub fn quorum_enabled(&self) -> bool {
+        self.config.quorum.is_some()
+    }
+
+    /// Latest prices and update data, either from the first endpoint that answers or, in
+    /// quorum mode, only for the feeds enough endpoints agree on
+    pub async fn fetch_snapshot(&self, feed_ids: &[String]) -> Result<PriceSnapshot> {
+        match &self.config.quorum {
+            Some(quorum) => self.fetch_quorum_snapshot(feed_ids, quorum).await,
+            None => {
+                self.with_failover(|url| async move {
+                    pyth_api::fetch_snapshot(&self.http_client, &url, feed_ids).await
+                })
+                .await
+            }
+        }
+    }
+
+    pub async fn fetch_prices(&self, feed_ids: &[String]) -> Result<PythPriceResponse> {
+        self.with_failover(|url| async move {
+            pyth_api::fetch_prices(&self.http_client, &url, feed_ids).await
+        })
+        .await
+    }
+
+    /// Subscribes to the price stream of the preferred endpoint, moving on to the next one
+    /// when it fails. Reconnects with exponential backoff until the receiver is dropped.
+    pub async fn stream_prices(
+        self: Arc<Self>,
+        feed_ids: Vec<String>,
+        stream_config: StreamConfig,
+        events: mpsc::Sender<StreamEvent>,
+    ) {
+        let initial_delay = Duration::from_secs(stream_config.reconnect_delay_seconds.max(1));
+        let max_delay =
+            Duration::from_secs(stream_config.max_reconnect_delay_seconds).max(initial_delay);
+        let idle_timeout = Duration::from_secs(stream_config.idle_timeout_seconds.max(1));
+        let mut delay = initial_delay;
+
+        loop {
+            let endpoint = self.endpoints_by_health()[0];
+            let mut connected = false;
+            let result = pyth_api::read_price_stream(
+                &self.http_client,
+                &endpoint.url,
+                &feed_ids,
+                idle_timeout,
+                &events,
+                &mut connected,
+            )
+            .await;
+
+            if events.is_closed() {
+                return;
+            }
+
+            if connected {
+                delay = initial_delay;
+            }
+
+            let mut retry_after = None;
+            let reason = match result {
+                Ok(()) => format!("stream closed by {}", endpoint.url),
+                Err(e) => {
+                    self.record_failure(endpoint, &e);
+                    retry_after = e.retry_after();
+                    e.to_string()
+                }
+            };
+            if events.send(StreamEvent::Disconnected(reason)).await.is_err() {
+                return;
+            }
+
+            tokio::time::sleep(retry_after.unwrap_or(delay).max(delay)).await;
+            delay = (delay * 2).min(max_delay);
+        }
+    }
+
+    async fn with_failover<T, F, Fut>(&self, request: F) -> Result<T>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut last_error = None;
+
+        for endpoint in self.endpoints_by_health() {
+            match self.with_timeout(&endpoint.url, request(endpoint.url.clone())).await {
+                Ok(value) => {
+                    self.record_success(endpoint);
+                    return Ok(value);
+                }
+                Err(e) => {
+                    self.record_failure(endpoint, &e);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(last_error.expect("load_config ensures at least one Hermes endpoint"))
+    }
+
+    async fn fetch_quorum_snapshot(
+        &self,
+        feed_ids: &[String],
+        quorum: &QuorumConfig,
+    ) -> Result<PriceSnapshot> {
+        let responses = join_all(self.endpoints.iter().map(|endpoint| async move {
+            let request = pyth_api::fetch_snapshot(&self.http_client, &endpoint.url, feed_ids);
+            let result = self.with_timeout(&endpoint.url, request).await;
+            match result {
+                Ok(snapshot) => {
+                    self.record_success(endpoint);
+                    Some(snapshot)
+                }
+                Err(e) => {
+                    self.record_failure(endpoint, &e);
+                    None
+                }
+            }
+        }))
+        .await;
+
+        let snapshots: Vec<PriceSnapshot> = responses.into_iter().flatten().collect();
+        if snapshots.len() < quorum.min_agreeing {
+            return Err(PythApiError::NoQuorum {
+                responded: snapshots.len(),
+                required: quorum.min_agreeing,
+            });
+        }
+
+        let mut agreed = PriceSnapshot::default();
+        for feed_id in feed_ids {
+            // Highest priority endpoint whose price enough endpoints (itself included) agree on
+            let reference = snapshots.iter().find(|candidate| {
+                let Some(price) = candidate.price(feed_id) else {
+                    return false;
+                };
+                let agreeing = snapshots
+                    .iter()
+                    .filter_map(|other| other.price(feed_id))
+                    .filter(|other| prices_agree(price, other, quorum.price_tolerance_bps))
+                    .count();
+                agreeing >= quorum.min_agreeing
+            });
+
+            match reference {
+                Some(snapshot) => agreed.insert_feed_from(snapshot, feed_id),
+                None => warn!(
+                    "No Hermes quorum for feed {} ({} endpoints required), skipping it this cycle",
+                    feed_id, quorum.min_agreeing
+                ),
+            }
+        }
+
+        Ok(agreed)
+    }
+
+    async fn with_timeout<T>(
+        &self,
+        url: &str,
+        request: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let timeout = Duration::from_secs(self.config.request_timeout_seconds);
+        tokio::time::timeout(timeout, request)
+            .await
+            .map_err(|_| PythApiError::Timeout { url: url.to_string(), timeout })?
+    }
+
+    /// Healthy endpoints first, then those still cooling down, each in priority order
+    fn endpoints_by_health(&self) -> Vec<&Endpoint> {
+        let now = Instant::now();
+        let (healthy, cooling_down): (Vec<&Endpoint>, Vec<&Endpoint>) =
+            self.endpoints.iter().partition(|endpoint| {
+                let health = endpoint.health.lock().unwrap();
+                health.unhealthy_until.is_none_or(|until| until <= now)
+            });
+
+        healthy.into_iter().chain(cooling_down).collect()
+    }
+
+    fn record_success(&self, endpoint: &Endpoint) {
+        let mut health = endpoint.health.lock().unwrap();
+        if health.consecutive_failures > 0 {
+            info!(
+                "Hermes endpoint {} recovered after {} failures",
+                endpoint.url, health.consecutive_failures
+            );
+        }
+        *health = EndpointHealth::default();
+    }
+
+    fn record_failure(&self, endpoint: &Endpoint, error: &PythApiError) {
+        let cooldown = error
+            .retry_after()
+            .unwrap_or(Duration::from_secs(self.config.failure_cooldown_seconds));
+        let mut health = endpoint.health.lock().unwrap();
+        health.consecutive_failures += 1;
+        health.unhealthy_until = Some(Instant::now() + cooldown);
+        warn!(
+            "Hermes endpoint {} failed ({} in a row), skipping it for {}s: {}",
+            endpoint.url,
+            health.consecutive_failures,
+            cooldown.as_secs(),
+            error
+        );
+    }
+}
+
+/// Same publish time and prices within `tolerance_bps` of each other
+fn prices_agree(a: &ParsedPrice, b: &ParsedPrice, tolerance_bps: u64) -> bool {
+    let (Ok(a), Ok(b)) = (pyth_api::parse_price(a), pyth_api::parse_price(b)) else {
+        return false;
+    };
+
+    a.publish_time == b.publish_time
+        && b.deviation_bps(&a).is_some_and(|deviation| deviation <= tolerance_bps)
+}
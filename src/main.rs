@@ -3,9 +3,21 @@ Provides an automated service for updating pyth feeds to then be used as push or
 Not the way PYTH was intended, but the way we ended up ¯\_(ツ)_/¯
 */
 
+mod accumulator;
+mod balance;
+mod budget;
 mod config;
 mod contract;
+mod fees;
+mod hermes;
+mod metrics;
+mod multicall;
+mod price;
+mod price_cache;
 mod pyth_api;
+mod remote_signer;
+mod signer;
+mod transactions;
 mod updater;
 mod utils;
 
@@ -17,7 +29,8 @@ async fn main() -> Result<()> {
     env_logger::init();
 
     let config = config::load_config().context("Failed to load config")?;
-    let mut updater = updater::PythUpdater::new(config);
+    let signers = signer::load_signers(&config)?;
+    let mut updater = updater::PythUpdater::new(config, signers);
     updater.run().await?;
 
     Ok(())
@@ -0,0 +1,128 @@
+//! In-process counters and gauges, rendered in the Prometheus text format. They are served
+//! at `/metrics` when `metrics_listen` is set, and logged under the `metrics` target after
+//! every cycle.
+
+use log::{info, warn};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    values: Mutex<BTreeMap<String, f64>>,
+}
+
+impl Metrics {
+    pub fn increment(&self, name: &str, labels: &[(&str, &str)]) {
+        *self.values.lock().unwrap().entry(series_key(name, labels)).or_default() += 1.0;
+    }
+
+    pub fn set_gauge(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.values.lock().unwrap().insert(series_key(name, labels), value);
+    }
+
+    pub fn render(&self) -> String {
+        let values = self.values.lock().unwrap();
+        values
+            .iter()
+            .map(|(key, value)| format!("{} {}", key, value))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Answers `GET /metrics` with the current values until the keeper stops
+pub async fn serve(metrics: Arc<Metrics>, listener: TcpListener) {
+    if let Ok(address) = listener.local_addr() {
+        info!("Serving metrics on http://{}/metrics", address);
+    }
+
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                warn!("Failed to accept metrics connection: {}", e);
+                continue;
+            }
+        };
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            if let Err(e) = respond(stream, &metrics).await {
+                warn!("Failed to answer metrics request: {}", e);
+            }
+        });
+    }
+}
+
+async fn respond(mut stream: TcpStream, metrics: &Metrics) -> std::io::Result<()> {
+    // Only the request line matters, scrapers send small requests
+    let mut request = [0; 1024];
+    let read = stream.read(&mut request).await?;
+    let request = String::from_utf8_lossy(&request[..read]);
+    let mut request_line = request.lines().next().unwrap_or_default().split_whitespace();
+    let (method, path) = (request_line.next(), request_line.next());
+
+    let (status, body) = match (method, path) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", format!("{}\n", metrics.render())),
+        _ => ("404 Not Found", String::new()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await
+}
+
+fn series_key(name: &str, labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return name.to_string();
+    }
+
+    let labels: Vec<String> =
+        labels.iter().map(|(key, value)| format!("{}=\"{}\"", key, value)).collect();
+    format!("{}{{{}}}", name, labels.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn get(address: std::net::SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn serves_prometheus_text() {
+        let metrics = Arc::new(Metrics::default());
+        metrics.increment("update_failures_total", &[("network", "Base"), ("reason", "timeout")]);
+        metrics.set_gauge("wallet_balance_native", &[("network", "Base")], 0.25);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(serve(metrics.clone(), listener));
+
+        let response = get(address, "/metrics").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.ends_with(
+            "update_failures_total{network=\"Base\",reason=\"timeout\"} 1\n\
+             wallet_balance_native{network=\"Base\"} 0.25\n"
+        ));
+
+        metrics.increment("update_failures_total", &[("network", "Base"), ("reason", "timeout")]);
+        assert!(get(address, "/metrics").await.contains("reason=\"timeout\"} 2\n"));
+
+        assert!(get(address, "/").await.starts_with("HTTP/1.1 404 Not Found"));
+    }
+}
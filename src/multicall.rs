@@ -0,0 +1,144 @@
+// Multicall3 is deployed at the same address on most EVM chains: https://www.multicall3.com
+
+use crate::contract::{IPythContract, PythError};
+use crate::price::PythPrice;
+use alloy::{
+    primitives::{address, Address, Bytes, B256},
+    providers::Provider,
+    sol,
+    sol_types::SolCall,
+    transports::Transport,
+};
+use anyhow::{anyhow, Result};
+use log::debug;
+
+pub const MULTICALL3_ADDRESS: Address = address!("cA11bde05977b3631167028862bE2a173976CA11");
+
+sol!(
+    #[sol(rpc)]
+    interface IMulticall3 {
+        struct Call3 {
+            address target;
+            bool allowFailure;
+            bytes callData;
+        }
+
+        struct Result {
+            bool success;
+            bytes returnData;
+        }
+
+        function aggregate3(Call3[] calldata calls) external payable returns (Result[] memory returnData);
+    }
+);
+
+/// A single price read against the Pyth contract
+#[derive(Debug, Clone, Copy)]
+pub enum PriceRead {
+    Spot(B256),
+    Ema(B256),
+}
+
+impl PriceRead {
+    fn call_data(self) -> Bytes {
+        match self {
+            Self::Spot(id) => IPythContract::getPriceUnsafeCall { id }.abi_encode().into(),
+            Self::Ema(id) => IPythContract::getEmaPriceUnsafeCall { id }.abi_encode().into(),
+        }
+    }
+
+    fn decode(self, data: &[u8]) -> Result<PythPrice> {
+        let price = match self {
+            Self::Spot(_) => {
+                IPythContract::getPriceUnsafeCall::abi_decode_returns(data, true)?.into()
+            }
+            Self::Ema(_) => {
+                IPythContract::getEmaPriceUnsafeCall::abi_decode_returns(data, true)?.into()
+            }
+        };
+        Ok(price)
+    }
+}
+
+/// Reads all prices with a single `aggregate3` call, falling back to one call per read
+/// when Multicall3 is not deployed on the network. Results are in the order of `reads`.
+pub async fn read_prices<T, P>(
+    provider: &P,
+    pyth_address: Address,
+    reads: &[PriceRead],
+) -> Vec<Result<PythPrice>>
+where
+    T: Transport + Clone,
+    P: Provider<T>,
+{
+    if reads.is_empty() {
+        return Vec::new();
+    }
+
+    match aggregate(provider, pyth_address, reads).await {
+        Ok(results) => results,
+        Err(e) => {
+            debug!("Multicall3 read failed ({}), reading {} prices individually", e, reads.len());
+            let contract = IPythContract::new(pyth_address, provider);
+            let mut results = Vec::with_capacity(reads.len());
+            for read in reads {
+                let result = match *read {
+                    PriceRead::Spot(id) => {
+                        contract.getPriceUnsafe(id).call().await.map(PythPrice::from)
+                    }
+                    PriceRead::Ema(id) => {
+                        contract.getEmaPriceUnsafe(id).call().await.map(PythPrice::from)
+                    }
+                };
+                results.push(result.map_err(Into::into));
+            }
+            results
+        }
+    }
+}
+
+async fn aggregate<T, P>(
+    provider: &P,
+    pyth_address: Address,
+    reads: &[PriceRead],
+) -> Result<Vec<Result<PythPrice>>>
+where
+    T: Transport + Clone,
+    P: Provider<T>,
+{
+    let calls = reads
+        .iter()
+        .map(|read| IMulticall3::Call3 {
+            target: pyth_address,
+            allowFailure: true,
+            callData: read.call_data(),
+        })
+        .collect();
+
+    // Without code at the address the call "succeeds" with empty return data, which fails
+    // to decode and sends us to the fallback
+    let multicall = IMulticall3::new(MULTICALL3_ADDRESS, provider);
+    let results = multicall.aggregate3(calls).call().await?.returnData;
+    if results.len() != reads.len() {
+        return Err(anyhow!(
+            "Multicall3 returned {} results for {} calls",
+            results.len(),
+            reads.len()
+        ));
+    }
+
+    Ok(reads
+        .iter()
+        .zip(results)
+        .map(|(read, result)| {
+            if result.success {
+                read.decode(&result.returnData)
+            } else {
+                match PythError::decode(&result.returnData) {
+                    Some(error) => Err(anyhow!("call reverted with {}", error)),
+                    None => Err(anyhow!("call reverted (0x{})", hex::encode(&result.returnData))),
+                }
+            }
+        })
+        .collect())
+}
@@ -0,0 +1,107 @@
+/// A Pyth price in its native fixed-point form, the real value being `price * 10^expo`.
+/// All comparisons are done on the integers, `to_f64` is only meant for display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PythPrice {
+    pub price: i64,
+    pub conf: u64,
+    pub expo: i32,
+    pub publish_time: i64,
+}
+
+impl PythPrice {
+    pub fn to_f64(self) -> f64 {
+        (self.price as f64) * 10_f64.powi(self.expo)
+    }
+
+    /// Confidence interval relative to the price, e.g. 0.01 when conf is 1% of the price
+    pub fn conf_ratio(&self) -> f64 {
+        if self.price == 0 {
+            return f64::INFINITY;
+        }
+        self.conf as f64 / self.price.unsigned_abs() as f64
+    }
+
+    /// Exact `|self - reference| / |reference|` in basis points, rounded down.
+    /// `None` when the reference is zero or the exponents are too far apart to compare.
+    pub fn deviation_bps(&self, reference: &PythPrice) -> Option<u64> {
+        let expo = self.expo.min(reference.expo);
+        let current = rescale(self.price, self.expo - expo)?;
+        let reference = rescale(reference.price, reference.expo - expo)?;
+        if reference == 0 {
+            return None;
+        }
+
+        let bps = (current - reference).checked_abs()?.checked_mul(10_000)? / reference.abs();
+        Some(u64::try_from(bps).unwrap_or(u64::MAX))
+    }
+}
+
+fn rescale(value: i64, extra_digits: i32) -> Option<i128> {
+    10_i128.checked_pow(extra_digits.try_into().ok()?)?.checked_mul(value as i128)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(price: i64, expo: i32) -> PythPrice {
+        PythPrice { price, conf: 0, expo, publish_time: 0 }
+    }
+
+    #[test]
+    fn deviation_with_same_exponent() {
+        assert_eq!(price(10_100, -2).deviation_bps(&price(10_000, -2)), Some(100));
+        assert_eq!(price(9_900, -2).deviation_bps(&price(10_000, -2)), Some(100));
+        assert_eq!(price(10_000, -2).deviation_bps(&price(10_000, -2)), Some(0));
+    }
+
+    #[test]
+    fn deviation_rounds_down() {
+        // 0.99 bps
+        assert_eq!(price(1_000_099, -4).deviation_bps(&price(1_000_000, -4)), Some(0));
+        assert_eq!(price(10_099, -2).deviation_bps(&price(10_000, -2)), Some(99));
+    }
+
+    #[test]
+    fn deviation_across_exponents() {
+        // 1.01 against 1.000000
+        assert_eq!(price(101, -2).deviation_bps(&price(1_000_000, -6)), Some(100));
+        assert_eq!(price(1_010_000, -6).deviation_bps(&price(100, -2)), Some(100));
+        // 1.000001 is 0.01 bps away from 1.00
+        assert_eq!(price(1_000_001, -6).deviation_bps(&price(100, -2)), Some(0));
+        assert_eq!(price(3, 2).deviation_bps(&price(250, 0)), Some(2_000));
+    }
+
+    #[test]
+    fn deviation_of_negative_prices() {
+        assert_eq!(price(-101, 0).deviation_bps(&price(-100, 0)), Some(100));
+        assert_eq!(price(-99, 0).deviation_bps(&price(-100, 0)), Some(100));
+        // Crossing zero
+        assert_eq!(price(50, 0).deviation_bps(&price(-100, 0)), Some(15_000));
+    }
+
+    #[test]
+    fn deviation_against_zero_reference() {
+        assert_eq!(price(100, -2).deviation_bps(&price(0, -2)), None);
+        assert_eq!(price(0, -2).deviation_bps(&price(100, -2)), Some(10_000));
+    }
+
+    #[test]
+    fn deviation_when_rescaling_overflows() {
+        // 10^40 does not fit an i128
+        assert_eq!(price(1, 0).deviation_bps(&price(1, -40)), None);
+        // Rescaled value fits, scaling it to bps does not
+        assert_eq!(price(i64::MAX, 0).deviation_bps(&price(1, -19)), None);
+        // Largest values that still compare
+        assert_eq!(price(i64::MAX, 0).deviation_bps(&price(i64::MAX, 0)), Some(0));
+        assert_eq!(price(i64::MIN, 0).deviation_bps(&price(-1, 0)), Some(u64::MAX));
+    }
+
+    #[test]
+    fn conf_ratio() {
+        let mut eth = price(262_500_000_000, -8);
+        eth.conf = 1_312_500_000;
+        assert_eq!(eth.conf_ratio(), 0.005);
+        assert_eq!(price(0, -8).conf_ratio(), f64::INFINITY);
+    }
+}
@@ -0,0 +1,261 @@
+//! Latest Hermes prices shared by all network loops: a single stream subscription, polls
+//! that networks running close together share instead of each hitting Hermes, and the
+//! freshness of each feed's source data.
+
+use crate::config::{FeedConfig, HermesConfig, StreamConfig};
+use crate::hermes::HermesClient;
+use crate::pyth_api::{PriceSnapshot, PythApiError, StreamEvent};
+use crate::utils::{self, alert};
+use chrono::Utc;
+use log::{error, info, warn};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::{mpsc, watch, Mutex};
+
+pub struct PriceCache {
+    hermes: Arc<HermesClient>,
+    feeds: Vec<FeedConfig>,
+    feed_ids: Vec<String>,
+    max_retries: u32,
+    max_age: Duration,
+    /// Held across the fetch, so concurrent polls wait for one request instead of sending
+    /// their own
+    polled: Mutex<Option<(Instant, PriceSnapshot)>>,
+    streamed: watch::Sender<StreamedPrices>,
+    /// Keyed by feed id, observed once per new snapshot whichever network asked for it
+    sources: std::sync::Mutex<HashMap<String, SourceState>>,
+}
+
+/// Prices received over the Hermes stream, merged across events since the last (re)connect
+#[derive(Debug, Clone, Default)]
+pub struct StreamedPrices {
+    /// Only set once the current connection delivered prices
+    pub connected: bool,
+    pub snapshot: PriceSnapshot,
+}
+
+#[derive(Debug, Clone, Default)]
+struct SourceState {
+    last_publish_time: i64,
+    unchanged_snapshots: u32,
+    stale: bool,
+}
+
+impl SourceState {
+    /// Records the latest Hermes publish time, alerting when the feed's source turns stale
+    fn observe(&mut self, feed: &FeedConfig, publish_time: i64) {
+        if publish_time > self.last_publish_time {
+            self.last_publish_time = publish_time;
+            self.unchanged_snapshots = 0;
+        } else {
+            self.unchanged_snapshots += 1;
+        }
+
+        let age = Utc::now().timestamp() - publish_time;
+        let stale_reason = if feed.max_source_age_seconds.is_some_and(|max| age > max as i64) {
+            Some(format!("publish_time is {} old", utils::format_duration(age)))
+        } else if feed.max_unchanged_cycles.is_some_and(|max| self.unchanged_snapshots >= max) {
+            Some(format!(
+                "publish_time has not advanced for {} snapshots",
+                self.unchanged_snapshots
+            ))
+        } else {
+            None
+        };
+
+        match (&stale_reason, self.stale) {
+            (Some(reason), false) => {
+                alert!("SOURCE STALE: {} - {}, skipping on-chain updates", feed.symbol, reason)
+            }
+            (None, true) => info!("Source for {} is fresh again, resuming updates", feed.symbol),
+            _ => {}
+        }
+
+        self.stale = stale_reason.is_some();
+    }
+}
+
+impl PriceCache {
+    pub fn new(hermes: Arc<HermesClient>, feeds: Vec<FeedConfig>, config: &HermesConfig) -> Self {
+        let feed_ids = feeds.iter().map(|feed| feed.price_feed_id.clone()).collect();
+
+        Self {
+            hermes,
+            feeds,
+            feed_ids,
+            max_retries: config.max_retries,
+            max_age: Duration::from_secs(config.cache_max_age_seconds),
+            polled: Mutex::new(None),
+            streamed: watch::Sender::new(StreamedPrices::default()),
+            sources: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether the feed's Hermes data was stale in the latest snapshot
+    pub fn is_stale(&self, feed_id: &str) -> bool {
+        self.sources.lock().unwrap().get(feed_id).is_some_and(|source| source.stale)
+    }
+
+    fn observe_sources(&self, snapshot: &PriceSnapshot) {
+        let mut sources = self.sources.lock().unwrap();
+        for feed in &self.feeds {
+            if let Some(price_data) = snapshot.price(&feed.price_feed_id) {
+                let source = sources.entry(feed.price_feed_id.clone()).or_default();
+                source.observe(feed, price_data.price.publish_time);
+            }
+        }
+    }
+
+    /// Notified whenever streamed prices change
+    pub fn subscribe(&self) -> watch::Receiver<StreamedPrices> {
+        self.streamed.subscribe()
+    }
+
+    /// The streamed prices while the stream is up, polled ones otherwise. In quorum mode a
+    /// streamed price only triggers the cycle, the snapshot acted upon is still cross-checked
+    /// across endpoints.
+    pub async fn latest(&self) -> Result<PriceSnapshot, PythApiError> {
+        {
+            let streamed = self.streamed.borrow();
+            if streamed.connected && !self.hermes.quorum_enabled() {
+                return Ok(streamed.snapshot.clone());
+            }
+        }
+
+        self.poll().await
+    }
+
+    /// Polled prices, fetched again once older than `cache_max_age_seconds`
+    async fn poll(&self) -> Result<PriceSnapshot, PythApiError> {
+        let mut polled = self.polled.lock().await;
+        if let Some((fetched_at, snapshot)) = polled.as_ref() {
+            if fetched_at.elapsed() < self.max_age {
+                return Ok(snapshot.clone());
+            }
+        }
+
+        info!("Fetching prices from Pyth Network");
+        let snapshot = self.fetch_snapshot_with_retry().await?;
+        self.observe_sources(&snapshot);
+        *polled = Some((Instant::now(), snapshot.clone()));
+
+        Ok(snapshot)
+    }
+
+    /// Retries transient Hermes failures, gives up right away on errors that would repeat
+    async fn fetch_snapshot_with_retry(&self) -> Result<PriceSnapshot, PythApiError> {
+        let mut attempt = 0;
+
+        loop {
+            match self.hermes.fetch_snapshot(&self.feed_ids).await {
+                Ok(snapshot) => return Ok(snapshot),
+                Err(e) if e.is_retryable() && attempt < self.max_retries => {
+                    attempt += 1;
+                    let delay = e.retry_after().unwrap_or(Duration::from_secs(1 << attempt));
+                    warn!(
+                        "Fetching prices failed ({}), retry {}/{} in {}s",
+                        e,
+                        attempt,
+                        self.max_retries,
+                        delay.as_secs()
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Follows the Hermes price stream, publishing every price change to the subscribers
+    pub async fn run_stream(self: Arc<Self>, stream_config: StreamConfig) {
+        let (tx, mut rx) = mpsc::channel(256);
+        tokio::spawn(self.hermes.clone().stream_prices(self.feed_ids.clone(), stream_config, tx));
+
+        while let Some(event) = rx.recv().await {
+            // Observed before subscribers are notified, so they act on the new freshness
+            self.streamed.send_if_modified(|streamed| {
+                let changed = apply_stream_event(event, streamed);
+                if changed {
+                    self.observe_sources(&streamed.snapshot);
+                }
+                changed
+            });
+        }
+
+        error!("Pyth price stream task stopped, polling only");
+        self.streamed.send_modify(|streamed| *streamed = StreamedPrices::default());
+    }
+}
+
+/// Applies a stream event to the streamed prices, returns whether any price changed
+fn apply_stream_event(event: StreamEvent, streamed: &mut StreamedPrices) -> bool {
+    match event {
+        StreamEvent::Connected => {
+            info!("Connected to Pyth price stream");
+            false
+        }
+        StreamEvent::Disconnected(reason) => {
+            if streamed.connected {
+                warn!("Pyth price stream dropped ({}), polling meanwhile", reason);
+            } else {
+                warn!("Pyth price stream unavailable ({}), polling meanwhile", reason);
+            }
+            // Prices from before the drop must not be pushed once the stream is back
+            *streamed = StreamedPrices::default();
+            false
+        }
+        StreamEvent::Prices(snapshot) => {
+            streamed.snapshot.merge(snapshot);
+            streamed.connected = true;
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed() -> FeedConfig {
+        FeedConfig {
+            price_feed_id: "ff".repeat(32),
+            symbol: "ETH/USD".to_string(),
+            deviation_threshold: 1.0,
+            heartbeat_seconds: 90,
+            networks: Vec::new(),
+            max_conf_ratio: None,
+            max_source_age_seconds: Some(60),
+            max_unchanged_cycles: Some(3),
+            trigger_source: Default::default(),
+            backup_grace_seconds: None,
+        }
+    }
+
+    #[test]
+    fn source_turns_stale_once_publish_time_stops_advancing() {
+        let feed = feed();
+        let mut source = SourceState::default();
+        let now = Utc::now().timestamp();
+
+        source.observe(&feed, now);
+        for _ in 0..2 {
+            source.observe(&feed, now);
+            assert!(!source.stale);
+        }
+        source.observe(&feed, now);
+        assert!(source.stale);
+
+        source.observe(&feed, now + 1);
+        assert!(!source.stale);
+    }
+
+    #[test]
+    fn source_turns_stale_when_too_old() {
+        let mut source = SourceState::default();
+        source.observe(&feed(), Utc::now().timestamp() - 61);
+        assert!(source.stale);
+    }
+}
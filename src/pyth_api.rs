@@ -1,22 +1,86 @@
+use crate::accumulator::AccumulatorUpdate;
+use crate::price::PythPrice;
 use alloy::primitives::Bytes;
-use anyhow::{Context, Result};
-use serde::Deserialize;
+use log::warn;
+use reqwest::{header::RETRY_AFTER, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{collections::HashMap, time::Duration};
+use thiserror::Error;
+use tokio::sync::mpsc;
+
+/// Errors of the Hermes client. Transport failures, server errors and rate limits are
+/// worth retrying, the rest will fail the same way again.
+#[derive(Debug, Error)]
+pub enum PythApiError {
+    #[error("request to {url} failed: {source}")]
+    Transport {
+        url: String,
+        #[source]
+        source: reqwest::Error,
+    },
+    #[error("request to {url} timed out after {}s", timeout.as_secs())]
+    Timeout { url: String, timeout: Duration },
+    #[error("{url} returned HTTP {status}: {body}")]
+    HttpStatus { url: String, status: StatusCode, body: String },
+    #[error("{url} rate limited the keeper (retry after {retry_after:?})")]
+    RateLimited { url: String, retry_after: Option<Duration> },
+    #[error("failed to decode {what}: {reason}")]
+    Decode { what: String, reason: String },
+    #[error("no {what} for feed {feed_id}")]
+    MissingFeed { feed_id: String, what: &'static str },
+    #[error("Hermes quorum not reached: {responded} endpoints answered, {required} required")]
+    NoQuorum { responded: usize, required: usize },
+}
+
+impl PythApiError {
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Transport { .. } | Self::Timeout { .. } | Self::RateLimited { .. } => true,
+            Self::HttpStatus { status, .. } => status.is_server_error(),
+            Self::NoQuorum { .. } => true,
+            Self::Decode { .. } | Self::MissingFeed { .. } => false,
+        }
+    }
+
+    /// Delay requested by Hermes before the next attempt
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimited { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+
+    fn decode(what: impl Into<String>, reason: impl ToString) -> Self {
+        Self::Decode { what: what.into(), reason: reason.to_string() }
+    }
+}
+
+pub type Result<T, E = PythApiError> = std::result::Result<T, E>;
 
 #[derive(Debug, Deserialize)]
 pub struct PythPriceResponse {
+    pub binary: BinaryData,
     pub parsed: Vec<ParsedPrice>,
 }
 
 #[derive(Debug, Deserialize)]
+pub struct BinaryData {
+    pub data: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct ParsedPrice {
     pub id: String,
     pub price: PriceData,
+    pub ema_price: PriceData,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct PriceData {
     pub price: String,
+    pub conf: String,
     pub expo: i32,
+    pub publish_time: i64,
 }
 
 pub async fn fetch_prices(
@@ -26,7 +90,7 @@ pub async fn fetch_prices(
 ) -> Result<PythPriceResponse> {
     let feed_ids_with_prefix: Vec<String> = feed_ids.iter().map(|f| format!("0x{}", f)).collect();
     let url = format!(
-        "{}/v2/updates/price/latest?ids[]={}",
+        "{}/v2/updates/price/latest?parsed=true&encoding=hex&ids[]={}",
         hermes_url,
         feed_ids_with_prefix.join("&ids[]=")
     );
@@ -35,61 +99,225 @@ pub async fn fetch_prices(
         .get(&url)
         .send()
         .await
-        .context("Failed to fetch Pyth prices")?
-        .json::<PythPriceResponse>()
+        .map_err(|source| PythApiError::Transport { url: hermes_url.to_string(), source })?;
+    let response = check_status(hermes_url, response).await?;
+
+    let body = response
+        .bytes()
         .await
-        .context("Failed to parse Pyth response")?;
+        .map_err(|source| PythApiError::Transport { url: hermes_url.to_string(), source })?;
 
-    Ok(response)
+    parse_json("Hermes price response", &body)
 }
 
-pub async fn fetch_price_update_data(
+/// Maps non-success statuses to typed errors, honouring `Retry-After` on rate limits
+async fn check_status(hermes_url: &str, response: reqwest::Response) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+            .map(Duration::from_secs);
+        return Err(PythApiError::RateLimited { url: hermes_url.to_string(), retry_after });
+    }
+
+    let mut body = response.text().await.unwrap_or_default();
+    body.truncate(200);
+    Err(PythApiError::HttpStatus { url: hermes_url.to_string(), status, body })
+}
+
+fn parse_json<T: DeserializeOwned>(what: &str, body: &[u8]) -> Result<T> {
+    serde_json::from_slice(body).map_err(|e| PythApiError::decode(what, e))
+}
+
+/// Parsed prices together with the signed update data they were parsed from, so the
+/// update decision and the pushed transaction always refer to the same Hermes response.
+/// Feed ids are keyed without the `0x` prefix, as used in the config.
+#[derive(Debug, Clone, Default)]
+pub struct PriceSnapshot {
+    prices: HashMap<String, ParsedPrice>,
+    update_data: HashMap<String, Bytes>,
+}
+
+impl PriceSnapshot {
+    pub fn from_response(response: PythPriceResponse) -> Result<Self> {
+        let mut update_data = HashMap::new();
+        for hex_str in &response.binary.data {
+            let blob = Bytes::from(
+                hex::decode(hex_str.trim_start_matches("0x"))
+                    .map_err(|e| PythApiError::decode("price update data", e))?,
+            );
+            let update = AccumulatorUpdate::parse(&blob)
+                .map_err(|e| PythApiError::decode("price update data", e))?;
+            for feed_id in update.feed_ids() {
+                update_data.insert(feed_id.to_string(), blob.clone());
+            }
+        }
+
+        let mut prices = HashMap::new();
+        for price in response.parsed {
+            let feed_id = price.id.trim_start_matches("0x").to_string();
+            if !update_data.contains_key(&feed_id) {
+                return Err(PythApiError::MissingFeed { feed_id, what: "update data" });
+            }
+            prices.insert(feed_id, price);
+        }
+
+        Ok(Self { prices, update_data })
+    }
+
+    /// Overwrites feeds present in `newer`, keeping the rest
+    pub fn merge(&mut self, newer: PriceSnapshot) {
+        self.prices.extend(newer.prices);
+        self.update_data.extend(newer.update_data);
+    }
+
+    /// Copies a single feed's price and update data from another snapshot
+    pub fn insert_feed_from(&mut self, other: &PriceSnapshot, feed_id: &str) {
+        if let (Some(price), Some(blob)) =
+            (other.prices.get(feed_id), other.update_data.get(feed_id))
+        {
+            self.prices.insert(feed_id.to_string(), price.clone());
+            self.update_data.insert(feed_id.to_string(), blob.clone());
+        }
+    }
+
+    pub fn price(&self, feed_id: &str) -> Option<&ParsedPrice> {
+        self.prices.get(feed_id)
+    }
+
+    /// Update data carrying exactly the given feeds, at the prices in this snapshot
+    pub fn update_data(&self, feed_ids: &[String]) -> Result<Vec<Bytes>> {
+        let mut feeds_by_blob: Vec<(&Bytes, Vec<String>)> = Vec::new();
+        for feed_id in feed_ids {
+            let blob = self.update_data.get(feed_id).ok_or_else(|| PythApiError::MissingFeed {
+                feed_id: feed_id.clone(),
+                what: "update data",
+            })?;
+            match feeds_by_blob.iter_mut().find(|(b, _)| *b == blob) {
+                Some((_, feeds)) => feeds.push(feed_id.clone()),
+                None => feeds_by_blob.push((blob, vec![feed_id.clone()])),
+            }
+        }
+
+        feeds_by_blob
+            .into_iter()
+            .map(|(blob, feeds)| {
+                let update = AccumulatorUpdate::parse(blob)
+                    .map_err(|e| PythApiError::decode("price update data", e))?;
+                Ok(update.retain(&feeds))
+            })
+            .collect()
+    }
+}
+
+/// Events sent by the Hermes price stream to the updater
+#[derive(Debug)]
+pub enum StreamEvent {
+    Connected,
+    Prices(PriceSnapshot),
+    Disconnected(String),
+}
+
+pub async fn fetch_snapshot(
+    http_client: &reqwest::Client,
+    hermes_url: &str,
+    feed_ids: &[String],
+) -> Result<PriceSnapshot> {
+    let response = fetch_prices(http_client, hermes_url, feed_ids).await?;
+    PriceSnapshot::from_response(response)
+}
+
+/// Reads Hermes `/v2/updates/price/stream` until the connection ends, or stays silent for
+/// `idle_timeout`, forwarding every price event. `connected` is set once Hermes accepted
+/// the subscription.
+pub async fn read_price_stream(
     http_client: &reqwest::Client,
     hermes_url: &str,
     feed_ids: &[String],
-) -> Result<Vec<Bytes>> {
-    let feed_ids_with_prefix: Vec<String> = feed_ids.iter().map(|id| format!("0x{}", id)).collect();
+    idle_timeout: Duration,
+    events: &mpsc::Sender<StreamEvent>,
+    connected: &mut bool,
+) -> Result<()> {
+    let feed_ids_with_prefix: Vec<String> = feed_ids.iter().map(|f| format!("0x{}", f)).collect();
     let url = format!(
-        "{}/v2/updates/price/latest?ids[]={}",
+        "{}/v2/updates/price/stream?parsed=true&encoding=hex&ids[]={}",
         hermes_url,
         feed_ids_with_prefix.join("&ids[]=")
     );
 
-    #[derive(Deserialize)]
-    struct UpdateResponse {
-        binary: BinaryData,
-    }
+    let timed_out = || PythApiError::Timeout { url: hermes_url.to_string(), timeout: idle_timeout };
+
+    let request = http_client.get(&url).header("Accept", "text/event-stream").send();
+    let response = tokio::time::timeout(idle_timeout, request)
+        .await
+        .map_err(|_| timed_out())?
+        .map_err(|source| PythApiError::Transport { url: hermes_url.to_string(), source })?;
+    let mut response = check_status(hermes_url, response).await?;
 
-    #[derive(Deserialize)]
-    struct BinaryData {
-        data: Vec<String>,
+    *connected = true;
+    if events.send(StreamEvent::Connected).await.is_err() {
+        return Ok(());
     }
 
-    let response = http_client
-        .get(&url)
-        .send()
-        .await
-        .context("Failed to fetch price update data")?
-        .json::<UpdateResponse>()
+    // Server-sent events: `data:` lines accumulate until a blank line ends the event
+    let mut buffer = String::new();
+    let mut data = String::new();
+
+    // A connection can go silent while TCP stays open, which would freeze the streamed prices
+    while let Some(chunk) = tokio::time::timeout(idle_timeout, response.chunk())
         .await
-        .context("Failed to parse update response")?;
-
-    let update_data: Vec<Bytes> = response
-        .binary
-        .data
-        .iter()
-        .map(|hex_str| {
-            let hex = hex_str.trim_start_matches("0x");
-            Bytes::from(hex::decode(hex).expect("Failed to decode hex"))
-        })
-        .collect();
-
-    Ok(update_data)
-}
-
-pub fn parse_price(price_data: &ParsedPrice) -> Result<f64> {
-    let price: i64 = price_data.price.price.parse().context("Failed to parse price")?;
-    let expo = price_data.price.expo;
-    let actual_price = (price as f64) * 10_f64.powi(expo);
-    Ok(actual_price)
+        .map_err(|_| timed_out())?
+        .map_err(|source| PythApiError::Transport { url: hermes_url.to_string(), source })?
+    {
+        buffer.push_str(&String::from_utf8_lossy(&chunk));
+
+        while let Some(newline) = buffer.find('\n') {
+            let line: String = buffer.drain(..=newline).collect();
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            if line.is_empty() {
+                if data.is_empty() {
+                    continue;
+                }
+                let snapshot =
+                    parse_json::<PythPriceResponse>("Hermes stream event", data.as_bytes())
+                        .and_then(PriceSnapshot::from_response);
+                match snapshot {
+                    Ok(snapshot) => {
+                        if events.send(StreamEvent::Prices(snapshot)).await.is_err() {
+                            return Ok(());
+                        }
+                    }
+                    Err(e) => warn!("Ignoring malformed Pyth stream event: {}", e),
+                }
+                data.clear();
+            } else if let Some(payload) = line.strip_prefix("data:") {
+                data.push_str(payload.trim_start());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn parse_price(price_data: &ParsedPrice) -> Result<PythPrice> {
+    to_pyth_price(&price_data.price, "price")
+}
+
+pub fn parse_ema_price(price_data: &ParsedPrice) -> Result<PythPrice> {
+    to_pyth_price(&price_data.ema_price, "EMA price")
+}
+
+fn to_pyth_price(data: &PriceData, what: &str) -> Result<PythPrice> {
+    let price = data.price.parse().map_err(|e| PythApiError::decode(what, e))?;
+    let conf = data.conf.parse().map_err(|e| PythApiError::decode(format!("{} conf", what), e))?;
+
+    Ok(PythPrice { price, conf, expo: data.expo, publish_time: data.publish_time })
 }
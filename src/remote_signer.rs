@@ -0,0 +1,180 @@
+// Remote signing over the web3signer eth1 API: https://docs.web3signer.consensys.io/reference/api/rest
+
+use alloy::{
+    consensus::SignableTransaction,
+    network::TxSigner,
+    primitives::{Address, ChainId, PrimitiveSignature as Signature},
+};
+use async_trait::async_trait;
+use serde::Serialize;
+use std::time::Duration;
+
+/// Signs transactions through `POST {url}/api/v1/eth1/sign/{address}`. The service signs the
+/// keccak hash of the posted data, so the RLP signing payload of the transaction is sent and
+/// the returned signature is checked against `address` before use.
+#[derive(Debug, Clone)]
+pub struct RemoteSigner {
+    http_client: reqwest::Client,
+    url: String,
+    address: Address,
+    chain_id: ChainId,
+}
+
+#[derive(Serialize)]
+struct SignRequest {
+    data: String,
+}
+
+impl RemoteSigner {
+    pub fn new(url: &str, address: Address, chain_id: ChainId, timeout: Duration) -> Self {
+        let http_client = reqwest::Client::builder().timeout(timeout).build().unwrap_or_default();
+
+        Self { http_client, url: url.trim_end_matches('/').to_string(), address, chain_id }
+    }
+
+    async fn sign_payload(&self, payload: &[u8]) -> Result<Signature, String> {
+        let url = format!("{}/api/v1/eth1/sign/{}", self.url, self.address);
+        let request = SignRequest { data: format!("0x{}", hex::encode(payload)) };
+
+        let response = self
+            .http_client
+            .post(&url)
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| format!("request to {} failed: {}", url, e))?;
+        let status = response.status();
+        let body = response.text().await.map_err(|e| format!("reading {} failed: {}", url, e))?;
+        if !status.is_success() {
+            return Err(format!("{} returned HTTP {}: {}", url, status, body.trim()));
+        }
+
+        // Plain text, or a JSON string depending on the Accept header handling
+        let signature = body.trim().trim_matches('"').trim_start_matches("0x");
+        let bytes = hex::decode(signature).map_err(|e| format!("invalid signature: {}", e))?;
+        Signature::from_raw(&bytes).map_err(|e| format!("invalid signature: {}", e))
+    }
+}
+
+#[async_trait]
+impl TxSigner<Signature> for RemoteSigner {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    async fn sign_transaction(
+        &self,
+        tx: &mut dyn SignableTransaction<Signature>,
+    ) -> alloy::signers::Result<Signature> {
+        if !tx.set_chain_id_checked(self.chain_id) {
+            return Err(alloy::signers::Error::TransactionChainIdMismatch {
+                signer: self.chain_id,
+                tx: tx.chain_id().unwrap_or_default(),
+            });
+        }
+
+        let signature = self
+            .sign_payload(&tx.encoded_for_signing())
+            .await
+            .map_err(alloy::signers::Error::other)?;
+
+        let signer = signature
+            .recover_address_from_prehash(&tx.signature_hash())
+            .map_err(alloy::signers::Error::other)?;
+        if signer != self.address {
+            return Err(alloy::signers::Error::other(format!(
+                "remote signer signed as {} instead of {}",
+                signer, self.address
+            )));
+        }
+
+        Ok(signature)
+    }
+}
+
+/// The example's web3signer stand-in, served in-process by the tests
+#[cfg(test)]
+#[allow(dead_code)]
+#[path = "../examples/mock_remote_signer.rs"]
+mod mock_remote_signer;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::{
+        consensus::TxEip1559,
+        primitives::{TxKind, U256},
+        signers::local::PrivateKeySigner,
+    };
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+
+    const CHAIN_ID: ChainId = 8453;
+
+    /// Mock signer on an ephemeral port, signing with `key` for requests addressed to `address`
+    async fn mock_signer(key: &PrivateKeySigner, address: Address) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(mock_remote_signer::serve(listener, Arc::new(key.clone()), address));
+        url
+    }
+
+    fn transaction() -> TxEip1559 {
+        TxEip1559 {
+            chain_id: CHAIN_ID,
+            nonce: 7,
+            gas_limit: 120_000,
+            max_fee_per_gas: 2_000_000_000,
+            max_priority_fee_per_gas: 1_000_000,
+            to: TxKind::Call(Address::repeat_byte(0x42)),
+            value: U256::from(1),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn signs_as_configured_address() {
+        let key = PrivateKeySigner::random();
+        let url = mock_signer(&key, key.address()).await;
+        let signer = RemoteSigner::new(&url, key.address(), CHAIN_ID, Duration::from_secs(5));
+
+        let mut tx = transaction();
+        let signature = signer.sign_transaction(&mut tx).await.unwrap();
+
+        let recovered = signature.recover_address_from_prehash(&tx.signature_hash()).unwrap();
+        assert_eq!(recovered, key.address());
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_address() {
+        let key = PrivateKeySigner::random();
+        let url = mock_signer(&key, key.address()).await;
+        let other = PrivateKeySigner::random().address();
+        let signer = RemoteSigner::new(&url, other, CHAIN_ID, Duration::from_secs(5));
+
+        let error = signer.sign_transaction(&mut transaction()).await.unwrap_err();
+        assert!(error.to_string().contains("404"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn rejects_signature_of_another_account() {
+        // The service answers for `claimed` but signs with a different key
+        let key = PrivateKeySigner::random();
+        let claimed = PrivateKeySigner::random().address();
+        let url = mock_signer(&key, claimed).await;
+        let signer = RemoteSigner::new(&url, claimed, CHAIN_ID, Duration::from_secs(5));
+
+        let error = signer.sign_transaction(&mut transaction()).await.unwrap_err();
+        assert!(error.to_string().contains("instead of"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn rejects_other_chain() {
+        let key = PrivateKeySigner::random();
+        let url = mock_signer(&key, key.address()).await;
+        let signer = RemoteSigner::new(&url, key.address(), 1, Duration::from_secs(5));
+
+        let error = signer.sign_transaction(&mut transaction()).await.unwrap_err();
+        assert!(matches!(error, alloy::signers::Error::TransactionChainIdMismatch { .. }));
+    }
+}
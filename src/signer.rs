@@ -0,0 +1,62 @@
+use crate::config::{Config, KeySource, KeystoreConfig};
+use crate::remote_signer::RemoteSigner;
+use alloy::{network::EthereumWallet, primitives::Address, signers::local::PrivateKeySigner};
+use anyhow::{Context, Result};
+use log::info;
+use std::{collections::HashMap, str::FromStr, time::Duration};
+
+/// Wallet of every network that has a key. Each keystore is decrypted (and its password
+/// prompted for) once, even when several networks share it.
+pub fn load_signers(config: &Config) -> Result<HashMap<String, EthereumWallet>> {
+    let mut keystores: HashMap<String, PrivateKeySigner> = HashMap::new();
+    let mut signers = HashMap::new();
+
+    for network in &config.networks {
+        let signer = match &network.key_source {
+            KeySource::None => continue,
+            KeySource::Remote(remote) => {
+                let address = Address::from_str(&remote.address).with_context(|| {
+                    format!("Invalid remote signer address for network {}", network.name)
+                })?;
+                info!("Signing on {} as {} through {}", network.name, address, remote.url);
+                let signer = RemoteSigner::new(
+                    &remote.url,
+                    address,
+                    network.chain_id,
+                    Duration::from_secs(remote.timeout_seconds),
+                );
+                signers.insert(network.name.clone(), EthereumWallet::from(signer));
+                continue;
+            }
+            KeySource::PrivateKey(private_key) => PrivateKeySigner::from_str(private_key)
+                .with_context(|| format!("Invalid private key for network {}", network.name))?,
+            KeySource::Keystore(keystore) => match keystores.get(&keystore.path) {
+                Some(signer) => signer.clone(),
+                None => {
+                    let signer = decrypt_keystore(keystore)?;
+                    keystores.insert(keystore.path.clone(), signer.clone());
+                    signer
+                }
+            },
+        };
+
+        info!("Signing on {} as {}", network.name, signer.address());
+        signers.insert(network.name.clone(), EthereumWallet::from(signer));
+    }
+
+    Ok(signers)
+}
+
+fn decrypt_keystore(keystore: &KeystoreConfig) -> Result<PrivateKeySigner> {
+    let password = match &keystore.password_file {
+        Some(path) => std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read keystore password file {}", path))?
+            .trim_end_matches(['\n', '\r'])
+            .to_string(),
+        None => rpassword::prompt_password(format!("Password for keystore {}: ", keystore.path))
+            .context("Failed to read keystore password")?,
+    };
+
+    PrivateKeySigner::decrypt_keystore(&keystore.path, password)
+        .with_context(|| format!("Failed to decrypt keystore {}", keystore.path))
+}
@@ -0,0 +1,215 @@
+use crate::config::NetworkConfig;
+use crate::contract::UpdateError;
+use crate::fees::GasPricing;
+use alloy::{
+    network::TransactionBuilder,
+    primitives::{TxHash, U256},
+    providers::{PendingTransactionBuilder, PendingTransactionError, Provider, WatchTxError},
+    rpc::types::{TransactionReceipt, TransactionRequest},
+    transports::Transport,
+};
+use anyhow::{bail, Context, Result};
+use log::{info, warn};
+use std::{sync::Mutex, time::Duration};
+
+/// Nonce and fees of the last transaction a signer broadcast, so a later cycle finding it
+/// still pending outbids it instead of being rejected as underpriced
+#[derive(Debug, Default)]
+pub struct LastSent(Mutex<Option<(u64, GasPricing)>>);
+
+impl LastSent {
+    fn record(&self, nonce: u64, pricing: GasPricing) {
+        *self.0.lock().unwrap() = Some((nonce, pricing));
+    }
+
+    fn pricing_for(&self, nonce: u64) -> Option<GasPricing> {
+        let last = *self.0.lock().unwrap();
+        last.filter(|(sent_nonce, _)| *sent_nonce == nonce).map(|(_, pricing)| pricing)
+    }
+}
+
+/// Sends `tx` and waits up to `receipt_timeout_seconds` for it to be mined. A transaction
+/// that doesn't make it in time is rebroadcast with the same nonce and bumped fees, and
+/// once `max_replacements` is used up it is optionally cancelled with a zero-value
+/// self-transfer. Send errors of the first attempt are returned as is.
+pub async fn send_with_replacement<T, P>(
+    provider: &P,
+    network: &NetworkConfig,
+    tx: TransactionRequest,
+    mut pricing: GasPricing,
+    last_sent: &LastSent,
+) -> Result<TransactionReceipt>
+where
+    T: Transport + Clone,
+    P: Provider<T>,
+{
+    let config = &network.replacement;
+    let timeout = Duration::from_secs(config.receipt_timeout_seconds);
+    let from = tx.from.context("Update transaction has no sender")?;
+
+    // The mined nonce, so a transaction left stuck by a previous cycle gets replaced too. It
+    // may carry bumped fees already, which the replacement has to outbid.
+    let nonce = provider.get_transaction_count(from).await.context("Failed to get nonce")?;
+    let pending_nonce =
+        provider.get_transaction_count(from).pending().await.context("Failed to get nonce")?;
+    if pending_nonce > nonce {
+        match last_sent.pricing_for(nonce) {
+            Some(previous) => {
+                let Some(bumped) = previous.bumped(config.fee_bump_percent, &network.fees) else {
+                    bail!(
+                        "Transaction with nonce {} still pending on {} and fee caps leave no room to replace it",
+                        nonce,
+                        network.name
+                    );
+                };
+                pricing = pricing.at_least(&bumped);
+                warn!(
+                    "Transaction with nonce {} still pending on {} from an earlier cycle, replacing it ({} -> {})",
+                    nonce, network.name, previous, pricing
+                );
+            }
+            None => warn!(
+                "Transaction with nonce {} pending on {} at unknown fees, the update may be rejected as underpriced until it is mined",
+                nonce, network.name
+            ),
+        }
+    }
+    let tx = tx.with_nonce(nonce);
+
+    let mut sent: Vec<TxHash> = Vec::new();
+    loop {
+        let hash = match provider.send_transaction(pricing.apply(tx.clone())).await {
+            Ok(pending_tx) => *pending_tx.tx_hash(),
+            Err(e) if sent.is_empty() => return Err(e.into()),
+            Err(e) => {
+                // Most likely "nonce too low": an earlier attempt got mined in the meantime
+                if let Some(receipt) = find_receipt(provider, &sent).await? {
+                    return Ok(receipt);
+                }
+                return Err(e).context("Failed to send replacement transaction");
+            }
+        };
+        sent.push(hash);
+        last_sent.record(nonce, pricing);
+
+        if let Some(receipt) = wait_for_receipt(provider, hash, timeout).await? {
+            return Ok(receipt);
+        }
+        if let Some(receipt) = find_receipt(provider, &sent).await? {
+            return Ok(receipt);
+        }
+
+        let replacements = sent.len() as u32 - 1;
+        let bumped = pricing.bumped(config.fee_bump_percent, &network.fees);
+        match bumped {
+            Some(bumped) if replacements < config.max_replacements => {
+                warn!(
+                    "Transaction {:?} on {} not mined after {}s, replacing with nonce {} ({}/{}): {} -> {}",
+                    hash,
+                    network.name,
+                    config.receipt_timeout_seconds,
+                    nonce,
+                    replacements + 1,
+                    config.max_replacements,
+                    pricing,
+                    bumped
+                );
+                pricing = bumped;
+            }
+            _ => break,
+        }
+    }
+
+    let stuck = sent.last().copied().unwrap_or_default();
+    if !config.cancel_stuck {
+        bail!(
+            "Transaction {:?} on {} not mined after {} attempts",
+            stuck,
+            network.name,
+            sent.len()
+        );
+    }
+
+    let Some(cancel_pricing) = pricing.bumped(config.fee_bump_percent, &network.fees) else {
+        bail!(
+            "Transaction {:?} on {} not mined and fee caps leave no room to cancel it",
+            stuck,
+            network.name
+        );
+    };
+    let cancel = TransactionRequest::default()
+        .with_from(from)
+        .with_to(from)
+        .with_value(U256::ZERO)
+        .with_nonce(nonce)
+        .with_gas_limit(21_000);
+
+    warn!(
+        "Cancelling transaction {:?} on {} with a self-transfer at nonce {} ({})",
+        stuck, network.name, nonce, cancel_pricing
+    );
+    let mut cancelled = None;
+    match provider.send_transaction(cancel_pricing.apply(cancel)).await {
+        Ok(pending_tx) => {
+            last_sent.record(nonce, cancel_pricing);
+            let hash = *pending_tx.tx_hash();
+            cancelled = wait_for_receipt(provider, hash, timeout).await?;
+            if cancelled.is_some() {
+                info!("Cancelled stuck transaction on {} with {:?}", network.name, hash);
+            }
+        }
+        Err(e) => warn!("Failed to send cancellation on {}: {}", network.name, e),
+    }
+
+    // The update may still have won the race against the cancellation
+    if let Some(receipt) = find_receipt(provider, &sent).await? {
+        return Ok(receipt);
+    }
+    match cancelled {
+        Some(receipt) => Err(UpdateError::Cancelled {
+            tx_hash: receipt.transaction_hash,
+            fee_wei: receipt.gas_used * receipt.effective_gas_price,
+        }
+        .into()),
+        None => bail!("Update on {} was not mined and has been cancelled", network.name),
+    }
+}
+
+/// `None` if the transaction was not mined within `timeout`
+async fn wait_for_receipt<T, P>(
+    provider: &P,
+    hash: TxHash,
+    timeout: Duration,
+) -> Result<Option<TransactionReceipt>>
+where
+    T: Transport + Clone,
+    P: Provider<T>,
+{
+    let pending_tx =
+        PendingTransactionBuilder::new(provider.root().clone(), hash).with_timeout(Some(timeout));
+
+    match pending_tx.get_receipt().await {
+        Ok(receipt) => Ok(Some(receipt)),
+        Err(PendingTransactionError::TxWatcher(WatchTxError::Timeout)) => Ok(None),
+        Err(e) => Err(e).context("Failed to get transaction receipt"),
+    }
+}
+
+/// Receipt of whichever of the transactions got mined
+async fn find_receipt<T, P>(provider: &P, hashes: &[TxHash]) -> Result<Option<TransactionReceipt>>
+where
+    T: Transport + Clone,
+    P: Provider<T>,
+{
+    for hash in hashes {
+        let receipt = provider
+            .get_transaction_receipt(*hash)
+            .await
+            .context("Failed to get transaction receipt")?;
+        if receipt.is_some() {
+            return Ok(receipt);
+        }
+    }
+
+    Ok(None)
+}
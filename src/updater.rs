@@ -1,43 +1,166 @@
-use crate::config::{Config, FeedConfig, NetworkConfig};
-use crate::contract::IPythContract;
-use crate::pyth_api;
-use crate::utils;
+use crate::balance::BalanceMonitor;
+use crate::budget::SpendBudgets;
+use crate::config::{
+    Config, FeedConfig, HeartbeatPolicy, NetworkConfig, PreflightPolicy, SubmissionMode,
+    TriggerSource,
+};
+use crate::contract::{IPythContract, PythError, UpdateError};
+use crate::fees;
+use crate::hermes::HermesClient;
+use crate::metrics::{self, Metrics};
+use crate::multicall::{self, PriceRead};
+use crate::price::PythPrice;
+use crate::price_cache::PriceCache;
+use crate::pyth_api::{self, PriceSnapshot, PythApiError};
+use crate::transactions::{self, LastSent};
+use crate::utils::{self, alert};
 use alloy::{
-    network::EthereumWallet,
-    primitives::{Address, FixedBytes},
-    providers::{Provider, ProviderBuilder},
-    signers::local::PrivateKeySigner,
+    eips::BlockId,
+    network::{EthereumWallet, TransactionBuilder},
+    primitives::{Address, Bytes, TxHash, B256, U256},
+    providers::{Provider, ProviderBuilder, RootProvider},
+    rpc::types::{Filter, TransactionRequest},
+    sol_types::SolEvent,
+    transports::{
+        http::{Client, Http},
+        Transport,
+    },
 };
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use chrono::{DateTime, Utc};
-use log::{error, info};
-use std::{collections::HashMap, str::FromStr, time::Duration};
+use log::{debug, error, info, warn};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{net::TcpListener, task::JoinSet, time::Instant};
 
 pub struct PythUpdater {
     config: Config,
-    http_client: reqwest::Client,
+    hermes: Arc<HermesClient>,
+    metrics: Arc<Metrics>,
+    budgets: Arc<SpendBudgets>,
+    balances: Arc<BalanceMonitor>,
+    summary: Arc<UpdateSummary>,
+    /// Keyed by network name
+    signers: HashMap<String, EthereumWallet>,
+}
+
+/// Update loop of a single network, with its own cadence, provider and feed states. Only the
+/// Hermes prices, metrics and spend tracking are shared with the other networks.
+struct NetworkUpdater {
+    network: NetworkConfig,
+    /// Feeds pushed to this network
+    feeds: Vec<FeedConfig>,
+    provider: RootProvider<Http<Client>>,
+    pyth_address: Address,
+    wallet: Option<EthereumWallet>,
+    prices: Arc<PriceCache>,
+    hermes: Arc<HermesClient>,
+    /// Keyed by feed id
     feed_states: HashMap<String, FeedState>,
+    /// Last block scanned for `PriceFeedUpdate` events
+    scanned_block: Option<u64>,
+    last_sent: LastSent,
+    poll_interval: Duration,
+    min_cycle_interval: Duration,
+    metrics: Arc<Metrics>,
+    budgets: Arc<SpendBudgets>,
+    balances: Arc<BalanceMonitor>,
+    summary: Arc<UpdateSummary>,
+}
+
+/// Update results of all networks since the last "Update summary" line, keyed by network name
+#[derive(Debug, Default)]
+struct UpdateSummary(std::sync::Mutex<BTreeMap<String, NetworkResults>>);
+
+#[derive(Debug, Default)]
+struct NetworkResults {
+    updated_feeds: usize,
+    already_fresh: u32,
+    failures: Vec<String>,
+}
+
+impl UpdateSummary {
+    fn record(&self, network_name: &str, update: impl FnOnce(&mut NetworkResults)) {
+        update(self.0.lock().unwrap().entry(network_name.to_string()).or_default());
+    }
+
+    /// One line across all networks, `None` when nothing was attempted since the last one
+    fn take(&self) -> Option<String> {
+        let results = std::mem::take(&mut *self.0.lock().unwrap());
+        if results.is_empty() {
+            return None;
+        }
+
+        let networks: Vec<String> = results
+            .into_iter()
+            .map(|(network_name, results)| {
+                let mut parts = Vec::new();
+                if results.updated_feeds > 0 {
+                    parts.push(format!("{} feeds updated", results.updated_feeds));
+                } else if results.already_fresh > 0 {
+                    parts.push("already fresh".to_string());
+                }
+                if !results.failures.is_empty() {
+                    parts.push(format!("failed ({})", results.failures.join(", ")));
+                }
+                format!("{}: {}", network_name, parts.join(", "))
+            })
+            .collect();
+
+        Some(networks.join(" | "))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UpdateReason {
+    Initial,
+    Heartbeat,
+    Deviation,
 }
 
 #[derive(Debug, Clone)]
 struct FeedState {
-    last_price: f64,
+    /// `None` until a price is known on-chain
+    last_price: Option<PythPrice>,
+    /// Only tracked for feeds that trigger on the EMA price
+    last_ema_price: Option<PythPrice>,
     last_on_chain_update: DateTime<Utc>,
+    /// Configured heartbeat, possibly shortened to the contract's valid time period
+    heartbeat_seconds: u64,
+    /// When the deviation threshold was first crossed against the current on-chain price
+    deviation_since: Option<DateTime<Utc>>,
+}
+
+impl FeedState {
+    fn set_on_chain(&mut self, price: PythPrice, ema_price: Option<PythPrice>) {
+        self.last_price = Some(price);
+        self.last_ema_price = ema_price;
+        self.last_on_chain_update =
+            DateTime::from_timestamp(price.publish_time, 0).unwrap_or_else(Utc::now);
+        self.deviation_since = None;
+    }
 }
 
 impl PythUpdater {
-    pub fn new(config: Config) -> Self {
-        let mut feed_states = HashMap::new();
-
-        for feed in &config.feeds {
-            for network_name in &feed.networks {
-                let state = FeedState { last_price: 0.0, last_on_chain_update: Utc::now() };
-                let key = utils::state_key(&feed.price_feed_id, network_name);
-                feed_states.insert(key, state);
-            }
-        }
+    pub fn new(config: Config, signers: HashMap<String, EthereumWallet>) -> Self {
+        let hermes = Arc::new(HermesClient::new(&config.pyth_hermes_urls, config.hermes.clone()));
+        let budgets = Arc::new(SpendBudgets::new(&config));
+        let balances = Arc::new(BalanceMonitor::new(&config));
 
-        Self { config, http_client: reqwest::Client::new(), feed_states }
+        Self {
+            config,
+            hermes,
+            metrics: Arc::new(Metrics::default()),
+            budgets,
+            balances,
+            summary: Arc::new(UpdateSummary::default()),
+            signers,
+        }
     }
 
     pub async fn run(&mut self) -> Result<()> {
@@ -48,68 +171,324 @@ impl PythUpdater {
             self.config.networks.len()
         );
 
-        if let Err(e) = self.initialize_feed_states().await {
-            error!("Failed to initialize feed states from on-chain: {}", e);
+        self.preflight().await?;
+
+        if let Some(listen) = &self.config.metrics_listen {
+            let listener = TcpListener::bind(listen)
+                .await
+                .with_context(|| format!("Failed to listen for metrics on {}", listen))?;
+            tokio::spawn(metrics::serve(self.metrics.clone(), listener));
         }
 
-        loop {
-            if let Err(e) = self.update_cycle().await {
-                error!("Error in update cycle: {}", e);
+        let prices = Arc::new(PriceCache::new(
+            self.hermes.clone(),
+            self.config.feeds.clone(),
+            &self.config.hermes,
+        ));
+
+        let mut network_updaters = Vec::new();
+        for network in &self.config.networks {
+            let mut updater = self.network_updater(network, &prices)?;
+            if updater.feeds.is_empty() {
+                info!("No feeds on {}, not scheduling updates", network.name);
+                continue;
             }
+            updater.check_valid_time_period().await?;
+            network_updaters.push(updater);
+        }
 
-            tokio::time::sleep(Duration::from_secs(self.config.poll_interval_seconds)).await;
+        if self.config.stream.enabled {
+            tokio::spawn(prices.clone().run_stream(self.config.stream.clone()));
         }
+
+        let summary_interval = Duration::from_secs(self.config.poll_interval_seconds.max(1));
+        tokio::spawn(Self::log_summaries(self.summary.clone(), summary_interval));
+
+        // One task per network, so a slow RPC or confirmation on one chain never delays
+        // the cycles of another
+        let mut loops = JoinSet::new();
+        let mut loop_networks = HashMap::new();
+        for updater in network_updaters {
+            let network_name = updater.network.name.clone();
+            let handle = loops.spawn(updater.run());
+            loop_networks.insert(handle.id(), network_name);
+        }
+
+        while let Some(result) = loops.join_next().await {
+            if let Err(e) = result {
+                let network_name = loop_networks.get(&e.id()).map_or("unknown", String::as_str);
+                alert!("Update loop on {} stopped: {}", network_name, e);
+            }
+        }
+
+        bail!("All network update loops stopped")
     }
 
-    async fn initialize_feed_states(&mut self) -> Result<()> {
-        if self.config.networks.is_empty() {
-            return Ok(());
+    /// Logs the results of all networks in one line every global `poll_interval_seconds`
+    async fn log_summaries(summary: Arc<UpdateSummary>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+
+        loop {
+            ticker.tick().await;
+            if let Some(line) = summary.take() {
+                info!("Update summary: {}", line);
+            }
         }
+    }
+
+    fn network_updater(
+        &self,
+        network: &NetworkConfig,
+        prices: &Arc<PriceCache>,
+    ) -> Result<NetworkUpdater> {
+        let feeds: Vec<FeedConfig> = self
+            .config
+            .feeds
+            .iter()
+            .filter(|feed| feed.networks.contains(&network.name))
+            .cloned()
+            .collect();
+
+        let feed_states = feeds
+            .iter()
+            .map(|feed| {
+                let state = FeedState {
+                    last_price: None,
+                    last_ema_price: None,
+                    last_on_chain_update: Utc::now(),
+                    heartbeat_seconds: feed.heartbeat_seconds,
+                    deviation_since: None,
+                };
+                (feed.price_feed_id.clone(), state)
+            })
+            .collect();
+
+        let poll_interval_seconds =
+            network.poll_interval_seconds.unwrap_or(self.config.poll_interval_seconds);
+        let min_cycle_interval_seconds = network
+            .min_cycle_interval_seconds
+            .unwrap_or(self.config.stream.min_cycle_interval_seconds);
+
+        Ok(NetworkUpdater {
+            network: network.clone(),
+            feeds,
+            provider: ProviderBuilder::new().on_http(network.rpc_url.parse()?),
+            pyth_address: Address::from_str(&network.pyth_contract)?,
+            wallet: self.signers.get(&network.name).cloned(),
+            prices: prices.clone(),
+            hermes: self.hermes.clone(),
+            feed_states,
+            scanned_block: None,
+            last_sent: LastSent::default(),
+            poll_interval: Duration::from_secs(poll_interval_seconds),
+            min_cycle_interval: Duration::from_secs(min_cycle_interval_seconds),
+            metrics: self.metrics.clone(),
+            budgets: self.budgets.clone(),
+            balances: self.balances.clone(),
+            summary: self.summary.clone(),
+        })
+    }
+
+    /// Verifies every network before anything is signed: the RPC serves the configured chain,
+    /// the Pyth contract is deployed and answers, and the signer holds funds. Failing networks
+    /// are refused or dropped according to their `preflight_policy`.
+    async fn preflight(&mut self) -> Result<()> {
+        let mut disabled = Vec::new();
 
         for network in &self.config.networks {
-            info!("Initializing feed states from {} on-chain data", network.name);
+            let signer = self.signers.get(&network.name).map(|w| w.default_signer().address());
+            let problems = match preflight_network(network, signer).await {
+                Ok(problems) => problems,
+                Err(e) => vec![e.to_string()],
+            };
+
+            if problems.is_empty() {
+                info!("Preflight passed on {} (chain id {})", network.name, network.chain_id);
+                continue;
+            }
 
-            let provider = ProviderBuilder::new().on_http(network.rpc_url.parse()?);
-            let pyth_address = Address::from_str(&network.pyth_contract)?;
-            let contract = IPythContract::new(pyth_address, &provider);
+            error!("Preflight failed on {}:", network.name);
+            for problem in &problems {
+                error!("  - {}", problem);
+            }
 
-            for feed in &self.config.feeds {
-                if !feed.networks.contains(&network.name) {
-                    continue;
+            match network.preflight_policy {
+                PreflightPolicy::Refuse => {
+                    bail!("Network {} failed preflight: {}", network.name, problems.join("; "))
                 }
+                PreflightPolicy::Disable => {
+                    warn!("Disabling {} until the keeper is restarted", network.name);
+                    disabled.push(network.name.clone());
+                }
+            }
+        }
 
-                let feed_id_bytes = hex::decode(&feed.price_feed_id)?;
-                let bytes32 = FixedBytes::<32>::from_slice(&feed_id_bytes);
-                let state_key = utils::state_key(&feed.price_feed_id, &network.name);
-
-                match contract.getPriceUnsafe(bytes32).call().await {
-                    Ok(result) => {
-                        let price = result.price;
-                        let expo = result.expo;
-                        let publish_time = result.publishTime;
-                        let actual_price = (price as f64) * 10_f64.powi(expo);
-                        let publish_datetime =
-                            DateTime::from_timestamp(publish_time.try_into().unwrap_or(0), 0)
-                                .unwrap_or_else(|| Utc::now());
-
-                        if let Some(state) = self.feed_states.get_mut(&state_key) {
-                            state.last_price = actual_price;
-                            state.last_on_chain_update = publish_datetime;
-                            info!(
-                                "Initialized {} on {} from on-chain: ${:.2} (published {}s ago)",
-                                feed.symbol,
-                                network.name,
-                                actual_price,
-                                (Utc::now() - publish_datetime).num_seconds()
-                            );
-                        }
+        if disabled.is_empty() {
+            return Ok(());
+        }
+
+        self.config.networks.retain(|network| !disabled.contains(&network.name));
+        if self.config.networks.is_empty() {
+            bail!("No network passed preflight");
+        }
+
+        Ok(())
+    }
+}
+
+impl NetworkUpdater {
+    async fn run(mut self) {
+        info!(
+            "Starting update loop on {} every {}s",
+            self.network.name,
+            self.poll_interval.as_secs()
+        );
+
+        if let Err(e) = self.initialize_feed_states().await {
+            error!("Failed to initialize feed states from {} on-chain: {}", self.network.name, e);
+        }
+
+        let mut streamed = self.prices.subscribe();
+        let mut last_cycle: Option<Instant> = None;
+        let mut next_poll = Instant::now();
+
+        loop {
+            tokio::select! {
+                // While the stream is up the tick only re-evaluates heartbeats on the
+                // streamed prices, otherwise the cache falls back to polling Hermes
+                _ = tokio::time::sleep_until(next_poll) => {}
+                Ok(()) = streamed.changed() => {
+                    let connected = streamed.borrow_and_update().connected;
+                    let throttled =
+                        last_cycle.is_some_and(|t| t.elapsed() < self.min_cycle_interval);
+                    if !connected || throttled {
+                        continue;
                     }
-                    Err(e) => {
-                        info!(
-                            "No on-chain price found for {} on {} ({}), will update on first cycle",
-                            feed.symbol, network.name, e
+                }
+            }
+
+            if let Err(e) = self.cycle().await {
+                error!("Error in update cycle on {}: {}", self.network.name, e);
+            }
+            last_cycle = Some(Instant::now());
+            next_poll = Instant::now() + self.poll_interval;
+        }
+    }
+
+    /// Reads the signer's native balance and classifies it against the network's thresholds
+    /// and runway
+    async fn check_balance(&self) {
+        let Some(wallet) = &self.wallet else {
+            return;
+        };
+        let address = wallet.default_signer().address();
+
+        let balance = match self.provider.get_balance(address).await {
+            Ok(balance) => f64::from(balance) / 1e18,
+            Err(e) => {
+                warn!("Failed to read signer balance on {}: {}", self.network.name, e);
+                return;
+            }
+        };
+
+        let report = self.balances.observe(&self.network.name, balance);
+        let runway = report.runway_days.map_or_else(
+            || "no recent spend".to_string(),
+            |days| format!("{:.1} days runway", days),
+        );
+        // Level changes are alerted by the monitor, the reading itself is routine
+        debug!(
+            "Signer {} on {}: {:.6} native ({}) - {:?}",
+            address, self.network.name, balance, runway, report.level
+        );
+
+        let labels = [("network", self.network.name.as_str())];
+        self.metrics.set_gauge("wallet_balance_native", &labels, balance);
+        self.metrics.set_gauge("wallet_balance_level", &labels, report.level as u8 as f64);
+        if let Some(days) = report.runway_days {
+            self.metrics.set_gauge("wallet_runway_days", &labels, days);
+        }
+    }
+
+    /// Makes sure heartbeats fire before consumers calling `getPriceNoOlderThan` with the
+    /// contract's valid time period start reverting
+    async fn check_valid_time_period(&mut self) -> Result<()> {
+        let network = &self.network;
+        let contract = IPythContract::new(self.pyth_address, &self.provider);
+
+        let valid_time_period: u64 = match contract.getValidTimePeriod().call().await {
+            Ok(result) => result.validTimePeriod.try_into().unwrap_or(u64::MAX),
+            Err(e) => {
+                warn!(
+                    "Could not read getValidTimePeriod on {} ({}), heartbeats are unchecked",
+                    network.name, e
+                );
+                return Ok(());
+            }
+        };
+        let max_heartbeat =
+            valid_time_period.saturating_sub(network.heartbeat_safety_margin_seconds);
+        info!(
+            "Valid time period on {} is {}s, heartbeats must be <= {}s",
+            network.name, valid_time_period, max_heartbeat
+        );
+
+        for feed in &self.feeds {
+            // A backup keeper only steps in once the grace is over as well
+            let grace = feed.backup_grace_seconds.or(network.backup_grace_seconds).unwrap_or(0);
+            if feed.heartbeat_seconds + grace <= max_heartbeat {
+                continue;
+            }
+            let with_grace = if grace > 0 { format!(" + {}s grace", grace) } else { String::new() };
+
+            match network.heartbeat_policy {
+                HeartbeatPolicy::Warn => warn!(
+                    "Heartbeat of {} on {} ({}s{}) exceeds the valid time period ({}s - {}s margin)",
+                    feed.symbol,
+                    network.name,
+                    feed.heartbeat_seconds,
+                    with_grace,
+                    valid_time_period,
+                    network.heartbeat_safety_margin_seconds
+                ),
+                HeartbeatPolicy::Refuse => bail!(
+                    "Heartbeat of {} on {} ({}s{}) exceeds the valid time period ({}s - {}s margin)",
+                    feed.symbol,
+                    network.name,
+                    feed.heartbeat_seconds,
+                    with_grace,
+                    valid_time_period,
+                    network.heartbeat_safety_margin_seconds
+                ),
+                HeartbeatPolicy::Derive => {
+                    // A heartbeat firing every cycle would push, and pay, without bound
+                    let derived = max_heartbeat.saturating_sub(grace);
+                    let poll_interval = self.poll_interval.as_secs();
+                    if derived == 0 || derived < poll_interval {
+                        bail!(
+                            "Cannot derive a heartbeat for {} on {}: the valid time period ({}s - {}s margin - {}s grace) leaves {}s, below the {}s poll interval",
+                            feed.symbol,
+                            network.name,
+                            valid_time_period,
+                            network.heartbeat_safety_margin_seconds,
+                            grace,
+                            derived,
+                            poll_interval
                         );
                     }
+                    if let Some(state) = self.feed_states.get_mut(&feed.price_feed_id) {
+                        state.heartbeat_seconds = derived;
+                    }
+                    info!(
+                        "Heartbeat of {} on {} lowered from {}s{} to {}s{} to fit the valid time period",
+                        feed.symbol,
+                        network.name,
+                        feed.heartbeat_seconds,
+                        with_grace,
+                        derived,
+                        with_grace
+                    );
                 }
             }
         }
@@ -117,185 +496,420 @@ impl PythUpdater {
         Ok(())
     }
 
-    async fn update_cycle(&mut self) -> Result<()> {
-        let feed_ids: Vec<String> =
-            self.config.feeds.iter().map(|f| f.price_feed_id.clone()).collect();
+    async fn initialize_feed_states(&mut self) -> Result<()> {
+        info!("Initializing feed states from {} on-chain data", self.network.name);
 
-        info!("----------------------------------------");
-        info!("Fetching prices from Pyth Network");
-        info!("----------------------------------------");
+        let feeds: Vec<&FeedConfig> = self.feeds.iter().collect();
+        let on_chain = read_on_chain_prices(&self.provider, self.pyth_address, &feeds).await?;
 
-        let response =
-            pyth_api::fetch_prices(&self.http_client, &self.config.pyth_hermes_url, &feed_ids)
-                .await?;
+        for (feed, result) in feeds.into_iter().zip(on_chain) {
+            match result {
+                Ok((on_chain_price, on_chain_ema)) => {
+                    let publish_datetime = DateTime::from_timestamp(on_chain_price.publish_time, 0)
+                        .unwrap_or_else(Utc::now);
 
-        let mut prices: HashMap<String, &pyth_api::ParsedPrice> = HashMap::new();
-        for price in &response.parsed {
-            let id = price.id.trim_start_matches("0x").to_string();
-            prices.insert(id, price);
+                    if let Some(state) = self.feed_states.get_mut(&feed.price_feed_id) {
+                        state.set_on_chain(on_chain_price, on_chain_ema);
+                        info!(
+                            "Initialized {} on {} from on-chain: ${:.2} (published {}s ago)",
+                            feed.symbol,
+                            self.network.name,
+                            on_chain_price.to_f64(),
+                            (Utc::now() - publish_datetime).num_seconds()
+                        );
+                    }
+                }
+                Err(e) => {
+                    info!(
+                        "No on-chain price found for {} on {} ({}), will update on first cycle",
+                        feed.symbol, self.network.name, e
+                    );
+                }
+            }
         }
 
-        let mut updates_by_network: HashMap<String, Vec<String>> = HashMap::new();
+        Ok(())
+    }
 
-        for network in &self.config.networks {
-            for feed in &self.config.feeds {
-                if !feed.networks.contains(&network.name) {
+    async fn cycle(&mut self) -> Result<()> {
+        let snapshot = self.prices.latest().await?;
+
+        self.update_cycle(&snapshot).await
+    }
+
+    /// Decides and pushes this network's updates from a single price snapshot
+    async fn update_cycle(&mut self, snapshot: &PriceSnapshot) -> Result<()> {
+        if self.network.track_external_updates {
+            if let Err(e) = self.sync_external_updates().await {
+                warn!("Failed to read PriceFeedUpdate events on {}: {:#}", self.network.name, e);
+            }
+        }
+
+        self.check_balance().await;
+
+        let network = &self.network;
+
+        let mut feeds_to_update = Vec::new();
+        // Backup interventions by feed id, only counted once the feed was actually pushed
+        let mut interventions: Vec<(String, String)> = Vec::new();
+
+        for feed in &self.feeds {
+            let Some(price_data) = snapshot.price(&feed.price_feed_id) else {
+                let error = PythApiError::MissingFeed {
+                    feed_id: feed.price_feed_id.clone(),
+                    what: "price",
+                };
+                warn!("✗ {:<12} on {:<10} | Skipping: {}", feed.symbol, network.name, error);
+                continue;
+            };
+
+            if self.prices.is_stale(&feed.price_feed_id) {
+                info!("✗ {:<12} on {:<10} | SOURCE STALE, skipping", feed.symbol, network.name);
+                continue;
+            }
+
+            let parsed = pyth_api::parse_price(price_data).and_then(|price| {
+                let ema = if feed.trigger_source.uses_ema() {
+                    Some(pyth_api::parse_ema_price(price_data)?)
+                } else {
+                    None
+                };
+                Ok((price, ema))
+            });
+            let (current_price, current_ema) = match parsed {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    warn!("✗ {:<12} on {:<10} | Skipping: {}", feed.symbol, network.name, e);
                     continue;
                 }
+            };
+            let state = self.feed_states.get_mut(&feed.price_feed_id).unwrap();
+            let backup_grace = feed.backup_grace_seconds.or(network.backup_grace_seconds);
 
-                if let Some(price_data) = prices.get(&feed.price_feed_id) {
-                    let current_price = pyth_api::parse_price(price_data)?;
-                    let state_key = utils::state_key(&feed.price_feed_id, &network.name);
-                    let state = self.feed_states.get(&state_key).unwrap();
-
-                    let deviation_pct = if state.last_price > 0.0 {
-                        ((current_price - state.last_price) / state.last_price).abs() * 100.0
-                    } else {
-                        0.0
-                    };
-
-                    let should_update = self.should_update_feed(feed, state, current_price)?;
-
-                    let time_since_publish = Utc::now() - state.last_on_chain_update;
-                    let seconds_ago = time_since_publish.num_seconds();
-                    let time_ago = utils::format_duration(seconds_ago);
-
-                    let is_stablecoin = feed.deviation_threshold <= 0.1;
-
-                    if should_update {
-                        if is_stablecoin {
-                            info!(
-                                "✓ {:<12} on {:<10} | Price: ${:>10.4} | Last: ${:>10.4} | Deviation: {:>7.4}% | Published: {:<8} ago | UPDATING",
-                                feed.symbol, network.name, current_price, state.last_price, deviation_pct, time_ago
-                            );
-                        } else {
-                            info!(
-                                "✓ {:<12} on {:<10} | Price: ${:>10.2} | Last: ${:>10.2} | Deviation: {:>7.4}% | Published: {:<8} ago | UPDATING",
-                                feed.symbol, network.name, current_price, state.last_price, deviation_pct, time_ago
-                            );
-                        }
-                        updates_by_network
-                            .entry(network.name.clone())
-                            .or_insert_with(Vec::new)
-                            .push(feed.price_feed_id.clone());
-                    } else {
-                        if is_stablecoin {
-                            info!(
-                                "○ {:<12} on {:<10} | Price: ${:>10.4} | Last: ${:>10.4} | Deviation: {:>7.4}% | Published: {:<8} ago | Skipping (threshold: {:.2}%)",
-                                feed.symbol, network.name, current_price, state.last_price, deviation_pct, time_ago, feed.deviation_threshold
-                            );
-                        } else {
-                            info!(
-                                "○ {:<12} on {:<10} | Price: ${:>10.2} | Last: ${:>10.2} | Deviation: {:>7.4}% | Published: {:<8} ago | Skipping (threshold: {:.2}%)",
-                                feed.symbol, network.name, current_price, state.last_price, deviation_pct, time_ago, feed.deviation_threshold
-                            );
-                        }
+            let deviation_bps =
+                trigger_deviation_bps(feed, state, &current_price, current_ema.as_ref())
+                    .unwrap_or(0);
+
+            let mut reason =
+                should_update_feed(feed, state, &current_price, current_ema.as_ref(), backup_grace);
+            let waiting_for_primary = reason.is_none() && state.deviation_since.is_some();
+
+            // A wide confidence band only holds back deviation updates, heartbeats
+            // still go through so the on-chain price never expires
+            let conf_ratio = current_price.conf_ratio();
+            let wide_conf = feed.max_conf_ratio.is_some_and(|max| conf_ratio > max);
+            let mut held_back = None;
+            if wide_conf {
+                let max_conf_pct = feed.max_conf_ratio.unwrap_or_default() * 100.0;
+                match reason {
+                    Some(UpdateReason::Deviation) => {
+                        warn!(
+                            "Holding back deviation update for {} on {}: conf/price {:.4}% > {:.4}%",
+                            feed.symbol, network.name, conf_ratio * 100.0, max_conf_pct
+                        );
+                        reason = None;
+                        held_back =
+                            Some(format!("Held back (conf/price: {:.4}%)", conf_ratio * 100.0));
                     }
+                    Some(reason) => warn!(
+                        "{:?} update for {} on {} pushed with wide confidence: conf/price {:.4}% > {:.4}%",
+                        reason, feed.symbol, network.name, conf_ratio * 100.0, max_conf_pct
+                    ),
+                    None => {}
                 }
             }
-        }
 
-        for network in &self.config.networks {
-            if let Some(feeds_to_update_on_network) = updates_by_network.get(&network.name) {
-                info!("Updating {} feeds on {}", feeds_to_update_on_network.len(), network.name);
+            // Over budget only heartbeats keep going
+            if reason == Some(UpdateReason::Deviation) {
+                if let Some(exhausted) = self.budgets.exhausted(&network.name) {
+                    reason = None;
+                    held_back = Some(format!("Held back ({} exhausted)", exhausted));
+                }
+            }
+            if reason == Some(UpdateReason::Deviation) && self.balances.is_critical(&network.name) {
+                reason = None;
+                held_back = Some("Held back (balance critical)".to_string());
+            }
+            let should_update = reason.is_some();
+
+            if let (Some(reason), Some(grace)) = (reason, backup_grace) {
+                if reason != UpdateReason::Initial {
+                    info!(
+                        "Backup stepping in for {} on {}: {:?} still unhandled after {}s grace",
+                        feed.symbol, network.name, reason, grace
+                    );
+                    let reason = format!("{:?}", reason).to_lowercase();
+                    interventions.push((feed.price_feed_id.clone(), reason));
+                }
+            }
+
+            let time_since_publish = Utc::now() - state.last_on_chain_update;
+            let seconds_ago = time_since_publish.num_seconds();
+            let time_ago = utils::format_duration(seconds_ago);
 
-                if let Err(e) =
-                    self.update_feeds_on_network(network, feeds_to_update_on_network).await
-                {
-                    error!("Failed to update feeds on {}: {}", network.name, e);
+            let is_stablecoin = feed.deviation_threshold <= 0.1;
+
+            // Display only, decisions above are made on the fixed-point values
+            let display_price = current_price.to_f64();
+            let display_last = state.last_price.map(|last| last.to_f64()).unwrap_or(0.0);
+            let deviation_pct = deviation_bps as f64 / 100.0;
+
+            if should_update {
+                let status = if wide_conf { "UPDATING (wide conf)" } else { "UPDATING" };
+                if is_stablecoin {
+                    info!(
+                        "✓ {:<12} on {:<10} | Price: ${:>10.4} | Last: ${:>10.4} | Deviation: {:>7.2}% | Published: {:<8} ago | {}",
+                        feed.symbol, network.name, display_price, display_last, deviation_pct, time_ago, status
+                    );
+                } else {
+                    info!(
+                        "✓ {:<12} on {:<10} | Price: ${:>10.2} | Last: ${:>10.2} | Deviation: {:>7.2}% | Published: {:<8} ago | {}",
+                        feed.symbol, network.name, display_price, display_last, deviation_pct, time_ago, status
+                    );
+                }
+                feeds_to_update.push(feed.price_feed_id.clone());
+            } else {
+                let status = if let Some(held_back) = held_back {
+                    held_back
+                } else if waiting_for_primary {
+                    format!("Waiting for primary ({}s grace)", backup_grace.unwrap_or(0))
+                } else {
+                    format!("Skipping (threshold: {:.2}%)", feed.deviation_threshold)
+                };
+                if is_stablecoin {
+                    info!(
+                        "○ {:<12} on {:<10} | Price: ${:>10.4} | Last: ${:>10.4} | Deviation: {:>7.2}% | Published: {:<8} ago | {}",
+                        feed.symbol, network.name, display_price, display_last, deviation_pct, time_ago, status
+                    );
                 } else {
-                    let provider = ProviderBuilder::new().on_http(network.rpc_url.parse()?);
-                    let pyth_address = Address::from_str(&network.pyth_contract)?;
-                    let contract = IPythContract::new(pyth_address, &provider);
-
-                    for feed_id in feeds_to_update_on_network {
-                        if let Some(price_data) = prices.get(feed_id) {
-                            let current_price = pyth_api::parse_price(price_data)?;
-                            let feed_id_bytes = hex::decode(feed_id)?;
-                            let bytes32 = FixedBytes::<32>::from_slice(&feed_id_bytes);
-
-                            match contract.getPriceUnsafe(bytes32).call().await {
-                                Ok(result) => {
-                                    let on_chain_publish_time: u64 =
-                                        result.publishTime.try_into().unwrap_or(0);
-                                    let publish_datetime =
-                                        DateTime::from_timestamp(on_chain_publish_time as i64, 0)
-                                            .unwrap_or_else(|| Utc::now());
-
-                                    let state_key = utils::state_key(feed_id, &network.name);
-                                    if let Some(state) = self.feed_states.get_mut(&state_key) {
-                                        state.last_price = current_price;
-                                        state.last_on_chain_update = publish_datetime;
-                                    }
-                                }
-                                Err(e) => {
-                                    error!(
-                                        "Failed to read on-chain publish time for {}: {}",
-                                        feed_id, e
-                                    );
-                                }
-                            }
+                    info!(
+                        "○ {:<12} on {:<10} | Price: ${:>10.2} | Last: ${:>10.2} | Deviation: {:>7.2}% | Published: {:<8} ago | {}",
+                        feed.symbol, network.name, display_price, display_last, deviation_pct, time_ago, status
+                    );
+                }
+            }
+        }
+
+        if !feeds_to_update.is_empty() {
+            info!("Updating {} feeds on {}", feeds_to_update.len(), self.network.name);
+
+            // Bounds a slow confirmation or hung RPC, so the loop gets back to its schedule
+            let timeout_seconds = self.network.update_timeout();
+            let result = tokio::time::timeout(
+                Duration::from_secs(timeout_seconds),
+                self.update_network(&feeds_to_update, snapshot),
+            )
+            .await
+            .unwrap_or_else(|_| Err(UpdateError::Timeout { seconds: timeout_seconds }.into()));
+
+            match result {
+                Ok(pushed) if pushed.is_empty() => {
+                    self.summary.record(&self.network.name, |results| results.already_fresh += 1)
+                }
+                Ok(pushed) => {
+                    self.summary.record(&self.network.name, |results| {
+                        results.updated_feeds += pushed.len()
+                    });
+
+                    // Feeds found already fresh were handled by the primary keeper after all
+                    for (feed_id, reason) in &interventions {
+                        if !pushed.contains(feed_id) {
+                            continue;
                         }
+                        let Some(feed) = self.feeds.iter().find(|f| &f.price_feed_id == feed_id)
+                        else {
+                            continue;
+                        };
+                        self.metrics.increment(
+                            "backup_interventions_total",
+                            &[
+                                ("network", &self.network.name),
+                                ("feed", &feed.symbol),
+                                ("reason", reason),
+                            ],
+                        );
                     }
                 }
+                Err(e) => {
+                    error!("Failed to update feeds on {}: {:#}", self.network.name, e);
+                    let reason = e
+                        .downcast_ref::<UpdateError>()
+                        .map_or_else(|| "other".to_string(), UpdateError::reason);
+                    self.metrics.increment(
+                        "update_failures_total",
+                        &[("network", &self.network.name), ("reason", &reason)],
+                    );
+                    self.summary
+                        .record(&self.network.name, |results| results.failures.push(reason));
+                }
             }
         }
 
+        debug!(target: "metrics", "\n{}", self.metrics.render());
         Ok(())
     }
 
-    fn should_update_feed(
-        &self,
-        feed: &FeedConfig,
-        state: &FeedState,
-        current_price: f64,
-    ) -> Result<bool> {
-        if state.last_price == 0.0 {
-            info!("Initial update for {} - no previous price on-chain", feed.symbol);
-            return Ok(true);
-        }
-
-        let time_since_update = Utc::now() - state.last_on_chain_update;
-        if time_since_update.num_seconds() >= feed.heartbeat_seconds as i64 {
-            info!(
-                "Heartbeat triggered for {} ({}s >= {}s)",
-                feed.symbol,
-                time_since_update.num_seconds(),
-                feed.heartbeat_seconds
-            );
-            return Ok(true);
+    /// Refreshes feed states from `PriceFeedUpdate` events emitted since the last scan, so
+    /// updates pushed by other keepers are not duplicated or compared against stale prices
+    async fn sync_external_updates(&mut self) -> Result<()> {
+        let network = &self.network;
+        let contract = IPythContract::new(self.pyth_address, &self.provider);
+
+        let latest_block = self.provider.get_block_number().await?;
+        let Some(scanned_block) = self.scanned_block else {
+            // Startup already read the on-chain state, only follow new blocks from here on
+            self.scanned_block = Some(latest_block);
+            return Ok(());
+        };
+
+        let feed_ids: Vec<B256> = self
+            .feeds
+            .iter()
+            .map(|feed| Ok(B256::from_str(&feed.price_feed_id)?))
+            .collect::<Result<_>>()?;
+
+        let mut from_block = scanned_block + 1;
+        while from_block <= latest_block {
+            let to_block = latest_block.min(from_block + network.max_log_block_range.max(1) - 1);
+            let filter = Filter::new()
+                .address(self.pyth_address)
+                .event_signature(IPythContract::PriceFeedUpdate::SIGNATURE_HASH)
+                .topic1(feed_ids.clone())
+                .from_block(from_block)
+                .to_block(to_block);
+
+            for log in self.provider.get_logs(&filter).await? {
+                let tx_hash = log.transaction_hash.unwrap_or_default();
+                let event = log.log_decode::<IPythContract::PriceFeedUpdate>()?.inner.data;
+                let feed_id = hex::encode(event.id);
+                let Some(feed) = self.feeds.iter().find(|f| f.price_feed_id == feed_id) else {
+                    continue;
+                };
+                let Some(state) = self.feed_states.get_mut(&feed_id) else {
+                    continue;
+                };
+
+                let publish_time = event.publishTime as i64;
+                if state.last_price.is_some_and(|last| last.publish_time >= publish_time) {
+                    continue;
+                }
+
+                // The event carries no exponent, re-read the feed when we don't know it yet
+                let on_chain_price = match state.last_price {
+                    Some(last) => PythPrice {
+                        price: event.price,
+                        conf: event.conf,
+                        expo: last.expo,
+                        publish_time,
+                    },
+                    None => PythPrice::from(contract.getPriceUnsafe(event.id).call().await?),
+                };
+                let on_chain_ema = if feed.trigger_source.uses_ema() {
+                    let result = contract.getEmaPriceUnsafe(event.id).call().await;
+                    result.ok().map(PythPrice::from)
+                } else {
+                    None
+                };
+
+                state.set_on_chain(on_chain_price, on_chain_ema);
+                info!(
+                    "{} on {} updated externally to ${:.4} (tx {:?})",
+                    feed.symbol,
+                    network.name,
+                    on_chain_price.to_f64(),
+                    tx_hash
+                );
+            }
+
+            self.scanned_block = Some(to_block);
+            from_block = to_block + 1;
         }
 
-        let price_change_pct =
-            ((current_price - state.last_price) / state.last_price).abs() * 100.0;
-        if price_change_pct >= feed.deviation_threshold {
-            return Ok(true);
+        Ok(())
+    }
+
+    /// Pushes the feeds to the network and reads back their new on-chain prices
+    async fn update_network(
+        &mut self,
+        feed_ids: &[String],
+        snapshot: &PriceSnapshot,
+    ) -> Result<Vec<String>> {
+        let pushed = self.update_feeds_on_network(feed_ids, snapshot).await?;
+
+        let feeds: Vec<&FeedConfig> =
+            self.feeds.iter().filter(|f| feed_ids.contains(&f.price_feed_id)).collect();
+        let on_chain = read_on_chain_prices(&self.provider, self.pyth_address, &feeds).await?;
+
+        for (feed, result) in feeds.into_iter().zip(on_chain) {
+            match result {
+                Ok((on_chain_price, on_chain_ema)) => {
+                    if let Some(state) = self.feed_states.get_mut(&feed.price_feed_id) {
+                        state.set_on_chain(on_chain_price, on_chain_ema);
+                    }
+                }
+                Err(e) => {
+                    error!(
+                        "Failed to read on-chain publish time for {} on {}: {}",
+                        feed.symbol, self.network.name, e
+                    );
+                }
+            }
         }
 
-        Ok(false)
+        Ok(pushed)
     }
 
+    /// Feeds actually pushed, none when all of them were already fresh on-chain
     async fn update_feeds_on_network(
         &self,
-        network: &NetworkConfig,
         feed_ids: &[String],
-    ) -> Result<()> {
-        let update_data = pyth_api::fetch_price_update_data(
-            &self.http_client,
-            &self.config.pyth_hermes_url,
-            feed_ids,
-        )
-        .await?;
-
-        let signer = PrivateKeySigner::from_str(&network.private_key)?;
-        let wallet = EthereumWallet::from(signer);
+        snapshot: &PriceSnapshot,
+    ) -> Result<Vec<String>> {
+        let network = &self.network;
+        let wallet = self.wallet.clone().context("No signer for network")?;
+        let signer_address = wallet.default_signer().address();
         let provider = ProviderBuilder::new()
             .with_recommended_fillers()
             .wallet(wallet)
-            .on_http(network.rpc_url.parse()?);
+            .on_provider(self.provider.clone());
 
-        let pyth_address = Address::from_str(&network.pyth_contract)?;
+        let pyth_address = self.pyth_address;
         let contract = IPythContract::new(pyth_address, &provider);
+        // The primary keeper may have landed the same or a newer price since we decided
+        let mut feeds_to_push = feed_ids.to_vec();
+        if network.submission_mode == SubmissionMode::IfNecessary {
+            let reads = feed_ids
+                .iter()
+                .map(|feed_id| Ok(PriceRead::Spot(B256::from_str(feed_id)?)))
+                .collect::<Result<Vec<_>>>()?;
+            let on_chain = multicall::read_prices(&provider, pyth_address, &reads).await;
+
+            let mut already_fresh = Vec::new();
+            for (feed_id, on_chain_price) in feed_ids.iter().zip(on_chain) {
+                let (Some(price_data), Ok(on_chain_price)) =
+                    (snapshot.price(feed_id), on_chain_price)
+                else {
+                    continue;
+                };
+                if on_chain_price.publish_time >= price_data.price.publish_time {
+                    already_fresh.push(feed_id.clone());
+                }
+            }
+
+            if !already_fresh.is_empty() {
+                info!(
+                    "Already fresh on {}, skipping: {}",
+                    network.name,
+                    self.feed_symbols(&already_fresh).join(", ")
+                );
+                feeds_to_push.retain(|feed_id| !already_fresh.contains(feed_id));
+            }
+            if feeds_to_push.is_empty() {
+                return Ok(Vec::new());
+            }
+        }
+
+        let update_data = snapshot.update_data(&feeds_to_push)?;
 
         let update_fee_result = contract
             .getUpdateFee(update_data.clone())
@@ -304,52 +918,468 @@ impl PythUpdater {
             .context("Failed to get update fee")?;
         let update_fee = update_fee_result.feeAmount;
 
-        let gas_price = provider.get_gas_price().await.context("Failed to get gas price")?;
+        let pricing = fees::estimate(&provider, &network.fees).await?;
 
-        let tx = contract.updatePriceFeeds(update_data).value(update_fee).gas_price(gas_price);
+        let call = match network.submission_mode {
+            SubmissionMode::IfNecessary => {
+                let mut price_ids = Vec::with_capacity(feeds_to_push.len());
+                let mut publish_times = Vec::with_capacity(feeds_to_push.len());
+                for feed_id in &feeds_to_push {
+                    let price_data =
+                        snapshot.price(feed_id).context("Feed missing from snapshot")?;
+                    price_ids.push(B256::from_str(feed_id)?);
+                    publish_times.push(price_data.price.publish_time.try_into().unwrap_or(0));
+                }
+                contract
+                    .updatePriceFeedsIfNecessary(update_data, price_ids, publish_times)
+                    .clear_decoder()
+            }
+            SubmissionMode::Always => contract.updatePriceFeeds(update_data).clear_decoder(),
+        };
 
-        let pending_tx = tx.send().await.context("Failed to send update transaction")?;
+        let tx = call.value(update_fee).into_transaction_request().with_from(signer_address);
 
-        let receipt =
-            pending_tx.get_receipt().await.context("Failed to get transaction receipt")?;
+        // A reverting transaction still costs gas, so simulate it at the exact value and
+        // update data first
+        let simulation = match provider.call(&tx).await {
+            Ok(_) => provider.estimate_gas(&tx).await,
+            Err(e) => Err(e),
+        };
+        let gas_estimate = match simulation {
+            Ok(gas) => gas,
+            Err(e) => match PythError::from_transport(&e) {
+                Some(PythError::NoFreshUpdate) => {
+                    info!(
+                        "Already fresh on {}, skipping: {}",
+                        network.name,
+                        self.feed_symbols(&feeds_to_push).join(", ")
+                    );
+                    return Ok(Vec::new());
+                }
+                Some(error) => return Err(UpdateError::Rejected(error).into()),
+                None => return Err(e).context("Update transaction simulation failed"),
+            },
+        };
 
-        let tx_fee_wei = receipt.gas_used * gas_price;
-        let tx_fee_native = tx_fee_wei as f64 / 1e18;
+        let mut gas_limit = (gas_estimate as f64 * network.gas_limit_multiplier).ceil() as u64;
+        if let Some(cap) = network.max_gas_limit {
+            if gas_estimate > cap {
+                return Err(UpdateError::GasCapExceeded { estimate: gas_estimate, cap }.into());
+            }
+            gas_limit = gas_limit.min(cap);
+        }
+        let tx = tx.with_gas_limit(gas_limit);
 
-        let native_price_usd = self.get_native_token_price(network).await.unwrap_or(0.0);
-        let tx_fee_usd =
-            if native_price_usd > 0.0 { tx_fee_native * native_price_usd } else { 0.0 };
+        info!(
+            "Sending update on {} (gas limit {} for an estimate of {}, {})",
+            network.name, gas_limit, gas_estimate, pricing
+        );
+        let sent = transactions::send_with_replacement(
+            &provider,
+            network,
+            tx.clone(),
+            pricing,
+            &self.last_sent,
+        )
+        .await;
+        let receipt = match sent {
+            Ok(receipt) => receipt,
+            Err(e) => {
+                if let Some(UpdateError::Cancelled { fee_wei, .. }) = e.downcast_ref() {
+                    self.record_spend(*fee_wei, U256::ZERO).await;
+                }
+                return Err(e).context("Failed to send update transaction");
+            }
+        };
+
+        // A reverted transaction still pays for its gas, only the update fee is refunded
+        let tx_fee_wei = receipt.gas_used * receipt.effective_gas_price;
+        let update_fee = if receipt.status() { update_fee } else { U256::ZERO };
+        let tx_fee_usd = self.record_spend(tx_fee_wei, update_fee).await;
 
-        let price_info =
-            if native_price_usd > 0.0 { format!("(${:.4})", tx_fee_usd) } else { String::new() };
+        if !receipt.status() {
+            let block = receipt.block_number.unwrap_or_default();
+            let reason = revert_reason(&provider, &tx, receipt.transaction_hash, block).await;
+            return Err(
+                UpdateError::Reverted { tx_hash: receipt.transaction_hash, block, reason }.into()
+            );
+        }
+
+        let tx_fee_native = tx_fee_wei as f64 / 1e18;
+        let price_info = tx_fee_usd.map_or_else(String::new, |usd| format!("(${:.4})", usd));
 
         info!(
-            "Feeds updated on {} at block {} | Tx: {}/tx/{:?} | Gas used: {} | Tx fee: {:.6} native {}",
+            "Feeds updated on {} at block {} | Tx: {}/tx/{:?} | Gas used: {} @ {:.4} gwei | Tx fee: {:.6} native {}",
             network.name,
             receipt.block_number.unwrap_or_default(),
             network.block_explorer,
             receipt.transaction_hash,
             receipt.gas_used,
+            fees::to_gwei(receipt.effective_gas_price),
             tx_fee_native,
             price_info
         );
 
-        Ok(())
+        Ok(feeds_to_push)
+    }
+
+    /// Counts a mined transaction against the signer's runway and the spend budgets, returns
+    /// its tx fee in USD when the native token price is known
+    async fn record_spend(&self, tx_fee_wei: u128, update_fee_wei: U256) -> Option<f64> {
+        let network = &self.network;
+        let tx_fee_native = tx_fee_wei as f64 / 1e18;
+        let spend_native = tx_fee_native + f64::from(update_fee_wei) / 1e18;
+
+        self.balances.record_spend(&network.name, spend_native);
+
+        let native_price_usd = self.get_native_token_price(network).await.unwrap_or(0.0);
+        if native_price_usd <= 0.0 {
+            warn!(
+                "Native token price unavailable, spend on {} not counted in budgets",
+                network.name
+            );
+            return None;
+        }
+        self.budgets.record(&network.name, spend_native * native_price_usd);
+
+        Some(tx_fee_native * native_price_usd)
+    }
+
+    fn feed_symbols(&self, feed_ids: &[String]) -> Vec<String> {
+        feed_ids
+            .iter()
+            .map(|feed_id| {
+                self.feeds
+                    .iter()
+                    .find(|feed| &feed.price_feed_id == feed_id)
+                    .map_or_else(|| feed_id.clone(), |feed| feed.symbol.clone())
+            })
+            .collect()
     }
 
     async fn get_native_token_price(&self, network: &NetworkConfig) -> Result<f64> {
-        let response = pyth_api::fetch_prices(
-            &self.http_client,
-            &self.config.pyth_hermes_url,
-            &[network.native_feed_id.clone()],
-        )
-        .await?;
+        let response =
+            self.hermes.fetch_prices(std::slice::from_ref(&network.native_feed_id)).await?;
 
         if let Some(price_data) = response.parsed.first() {
-            let actual_price = pyth_api::parse_price(price_data)?;
-            Ok(actual_price)
+            Ok(pyth_api::parse_price(price_data)?.to_f64())
         } else {
             Err(anyhow::anyhow!("No price data found for native token"))
         }
     }
 }
+
+/// Problems found with a network's configuration, empty if it is good to use
+async fn preflight_network(
+    network: &NetworkConfig,
+    signer: Option<Address>,
+) -> Result<Vec<String>> {
+    let provider = ProviderBuilder::new().on_http(network.rpc_url.parse()?);
+    let pyth_address = Address::from_str(&network.pyth_contract)?;
+    let contract = IPythContract::new(pyth_address, &provider);
+    let mut problems = Vec::new();
+
+    let chain_id = provider.get_chain_id().await.context("eth_chainId failed")?;
+    if chain_id != network.chain_id {
+        // Nothing else is meaningful against the wrong chain
+        problems.push(format!(
+            "RPC serves chain id {} but the network is configured as {}",
+            chain_id, network.chain_id
+        ));
+        return Ok(problems);
+    }
+
+    let code = provider.get_code_at(pyth_address).await.context("eth_getCode failed")?;
+    if code.is_empty() {
+        problems.push(format!("no contract code at pyth_contract {}", pyth_address));
+    } else {
+        if let Err(e) = contract.getValidTimePeriod().call().await {
+            problems.push(format!("pyth_contract does not answer getValidTimePeriod: {}", e));
+        }
+        if let Err(e) = contract.getUpdateFee(Vec::new()).call().await {
+            problems.push(format!("pyth_contract does not answer getUpdateFee: {}", e));
+        }
+    }
+
+    // Networks without feeds may have no key, they never sign
+    if let Some(signer) = signer {
+        let balance = provider.get_balance(signer).await.context("eth_getBalance failed")?;
+        if balance.is_zero() {
+            problems.push(format!("signer {} has no balance", signer));
+        }
+    }
+
+    Ok(problems)
+}
+
+/// Top-level call of a `callTracer` trace
+#[derive(Debug, Deserialize)]
+struct CallTrace {
+    #[serde(default)]
+    output: Option<Bytes>,
+}
+
+/// Pyth error a mined update reverted with. The trace shows the exact state the transaction
+/// ran on. Without `debug_traceTransaction` it is replayed on the block's post-state, which
+/// includes an update landed before it in the same block, then on the parent block.
+async fn revert_reason<T, P>(
+    provider: &P,
+    tx: &TransactionRequest,
+    tx_hash: TxHash,
+    block: u64,
+) -> Option<PythError>
+where
+    T: Transport + Clone,
+    P: Provider<T>,
+{
+    let params = (tx_hash, serde_json::json!({ "tracer": "callTracer" }));
+    match provider.raw_request::<_, CallTrace>("debug_traceTransaction".into(), params).await {
+        Ok(trace) => return trace.output.and_then(|output| PythError::decode(&output)),
+        Err(e) => debug!("debug_traceTransaction unavailable ({}), replaying {:?}", e, tx_hash),
+    }
+
+    for block in [block, block.saturating_sub(1)] {
+        let replay = provider.call(tx).block(BlockId::number(block)).await;
+        if let Some(reason) = replay.err().as_ref().and_then(PythError::from_transport) {
+            return Some(reason);
+        }
+    }
+
+    None
+}
+
+/// On-chain price and, when the feed's trigger needs it, EMA price
+type OnChainPrice = (PythPrice, Option<PythPrice>);
+
+/// On-chain price of every given feed, plus its EMA price when the feed's trigger needs it,
+/// batched into a single Multicall3 read
+async fn read_on_chain_prices<T, P>(
+    provider: &P,
+    pyth_address: Address,
+    feeds: &[&FeedConfig],
+) -> Result<Vec<Result<OnChainPrice>>>
+where
+    T: Transport + Clone,
+    P: Provider<T>,
+{
+    let mut reads = Vec::new();
+    for feed in feeds {
+        let id = B256::from_str(&feed.price_feed_id)?;
+        reads.push(PriceRead::Spot(id));
+        if feed.trigger_source.uses_ema() {
+            reads.push(PriceRead::Ema(id));
+        }
+    }
+
+    let mut results = multicall::read_prices(provider, pyth_address, &reads).await.into_iter();
+    let mut prices = Vec::with_capacity(feeds.len());
+    for feed in feeds {
+        let price = results.next().context("missing multicall result")?;
+        let ema = if feed.trigger_source.uses_ema() {
+            results.next().context("missing multicall result")?.ok()
+        } else {
+            None
+        };
+        prices.push(price.map(|price| (price, ema)));
+    }
+
+    Ok(prices)
+}
+
+/// `backup_grace` is set in backup-keeper mode: the primary keeper gets that many seconds past
+/// the heartbeat, or after the deviation first appeared, before we step in
+fn should_update_feed(
+    feed: &FeedConfig,
+    state: &mut FeedState,
+    current_price: &PythPrice,
+    current_ema: Option<&PythPrice>,
+    backup_grace: Option<u64>,
+) -> Option<UpdateReason> {
+    if state.last_price.is_none() {
+        info!("Initial update for {} - no previous price on-chain", feed.symbol);
+        return Some(UpdateReason::Initial);
+    }
+
+    let grace = backup_grace.unwrap_or(0) as i64;
+
+    let time_since_update = Utc::now() - state.last_on_chain_update;
+    if time_since_update.num_seconds() >= state.heartbeat_seconds as i64 + grace {
+        info!(
+            "Heartbeat triggered for {} ({}s >= {}s{})",
+            feed.symbol,
+            time_since_update.num_seconds(),
+            state.heartbeat_seconds,
+            backup_grace.map(|grace| format!(" + {}s grace", grace)).unwrap_or_default()
+        );
+        return Some(UpdateReason::Heartbeat);
+    }
+
+    // A zero on-chain price can't be compared against, treat it like a missing one
+    let deviation_bps =
+        trigger_deviation_bps(feed, state, current_price, current_ema).unwrap_or(u64::MAX);
+    if deviation_bps < feed.deviation_threshold_bps() {
+        state.deviation_since = None;
+        return None;
+    }
+
+    let deviation_since = *state.deviation_since.get_or_insert_with(Utc::now);
+    let persisted = (Utc::now() - deviation_since).num_seconds();
+    if persisted >= grace {
+        return Some(UpdateReason::Deviation);
+    }
+
+    debug!(
+        "Deviation on {} persisting for {}s, waiting {}s for the primary keeper",
+        feed.symbol, persisted, grace
+    );
+    None
+}
+
+/// Deviation of the feed's trigger source from its on-chain value. A feed triggering on the
+/// EMA falls back to the spot price while no on-chain EMA is known.
+fn trigger_deviation_bps(
+    feed: &FeedConfig,
+    state: &FeedState,
+    current_price: &PythPrice,
+    current_ema: Option<&PythPrice>,
+) -> Option<u64> {
+    let spot = state.last_price.and_then(|last| current_price.deviation_bps(&last));
+    let ema = match (current_ema, state.last_ema_price) {
+        (Some(current), Some(last)) => current.deviation_bps(&last),
+        _ => None,
+    };
+
+    match feed.trigger_source {
+        TriggerSource::Spot => spot,
+        TriggerSource::Ema => ema.or(spot),
+        TriggerSource::Max => spot.max(ema),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+
+    fn feed() -> FeedConfig {
+        FeedConfig {
+            price_feed_id: "ff".repeat(32),
+            symbol: "ETH/USD".to_string(),
+            deviation_threshold: 1.0,
+            heartbeat_seconds: 90,
+            networks: Vec::new(),
+            max_conf_ratio: None,
+            max_source_age_seconds: None,
+            max_unchanged_cycles: None,
+            trigger_source: TriggerSource::Spot,
+            backup_grace_seconds: None,
+        }
+    }
+
+    fn price(price: i64) -> PythPrice {
+        PythPrice { price, conf: 0, expo: -2, publish_time: Utc::now().timestamp() }
+    }
+
+    /// On-chain at 100.00, last updated `seconds_ago`
+    fn state(seconds_ago: i64) -> FeedState {
+        FeedState {
+            last_price: Some(price(10_000)),
+            last_ema_price: None,
+            last_on_chain_update: Utc::now() - TimeDelta::seconds(seconds_ago),
+            heartbeat_seconds: 90,
+            deviation_since: None,
+        }
+    }
+
+    #[test]
+    fn initial_update_skips_grace() {
+        let mut state = state(0);
+        state.last_price = None;
+        let reason = should_update_feed(&feed(), &mut state, &price(10_000), None, Some(300));
+        assert_eq!(reason, Some(UpdateReason::Initial));
+    }
+
+    #[test]
+    fn heartbeat_waits_for_grace() {
+        let feed = feed();
+        let current = price(10_000);
+
+        assert_eq!(
+            should_update_feed(&feed, &mut state(100), &current, None, None),
+            Some(UpdateReason::Heartbeat)
+        );
+        assert_eq!(should_update_feed(&feed, &mut state(100), &current, None, Some(30)), None);
+        assert_eq!(
+            should_update_feed(&feed, &mut state(120), &current, None, Some(30)),
+            Some(UpdateReason::Heartbeat)
+        );
+    }
+
+    #[test]
+    fn deviation_without_grace_updates_right_away() {
+        let mut state = state(0);
+        let reason = should_update_feed(&feed(), &mut state, &price(10_200), None, None);
+        assert_eq!(reason, Some(UpdateReason::Deviation));
+    }
+
+    #[test]
+    fn deviation_waits_for_grace() {
+        let feed = feed();
+        let mut state = state(0);
+        let current = price(10_200);
+
+        assert_eq!(should_update_feed(&feed, &mut state, &current, None, Some(30)), None);
+        let since = state.deviation_since.expect("deviation start recorded");
+
+        // Still within the grace, the first crossing is kept
+        assert_eq!(should_update_feed(&feed, &mut state, &current, None, Some(30)), None);
+        assert_eq!(state.deviation_since, Some(since));
+
+        state.deviation_since = Some(Utc::now() - TimeDelta::seconds(30));
+        assert_eq!(
+            should_update_feed(&feed, &mut state, &current, None, Some(30)),
+            Some(UpdateReason::Deviation)
+        );
+    }
+
+    #[test]
+    fn deviation_start_resets_once_deviation_is_gone() {
+        let feed = feed();
+        let mut state = state(0);
+
+        should_update_feed(&feed, &mut state, &price(10_200), None, Some(30));
+        assert!(state.deviation_since.is_some());
+
+        assert_eq!(should_update_feed(&feed, &mut state, &price(10_050), None, Some(30)), None);
+        assert_eq!(state.deviation_since, None);
+    }
+
+    #[test]
+    fn deviation_start_resets_on_new_on_chain_price() {
+        let feed = feed();
+        let mut state = state(0);
+
+        should_update_feed(&feed, &mut state, &price(10_200), None, Some(30));
+        assert!(state.deviation_since.is_some());
+
+        state.set_on_chain(price(10_200), None);
+        assert_eq!(state.deviation_since, None);
+        assert_eq!(should_update_feed(&feed, &mut state, &price(10_200), None, Some(30)), None);
+    }
+
+    #[test]
+    fn summarizes_all_networks_in_one_line() {
+        let summary = UpdateSummary::default();
+        assert_eq!(summary.take(), None);
+
+        summary.record("Ethereum", |results| results.already_fresh += 1);
+        summary.record("Base", |results| results.updated_feeds += 2);
+        summary.record("Base", |results| results.updated_feeds += 1);
+        summary.record("Base", |results| results.failures.push("Timeout".to_string()));
+        assert_eq!(
+            summary.take().as_deref(),
+            Some("Base: 3 feeds updated, failed (Timeout) | Ethereum: already fresh")
+        );
+        assert_eq!(summary.take(), None);
+    }
+}
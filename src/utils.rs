@@ -1,6 +1,11 @@
-pub fn state_key(feed_id: &str, network_name: &str) -> String {
-    format!("{}:{}", feed_id, network_name)
+/// Alert-level event, logged under its own `alert` target so it can be routed or filtered
+/// separately, e.g. `RUST_LOG=info,alert=error`
+macro_rules! alert {
+    ($($arg:tt)+) => {
+        log::error!(target: "alert", $($arg)+)
+    };
 }
+pub(crate) use alert;
 
 pub fn format_duration(seconds: i64) -> String {
     if seconds < 60 {